@@ -0,0 +1,261 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{Action, BuyUnit, MoveUnit, Upgrade};
+use crate::{building, resource, tile, unit, utils, world_gen};
+
+/// Something an empire could do right now, with its price and, if it can't
+/// be done, the reason why
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ActionOption {
+    pub action: Action,
+    pub cost: Vec<(resource::Resource, i32)>,
+    /// `None` for legal actions
+    pub illegal_reason: Option<String>,
+}
+
+impl ActionOption {
+    pub fn is_legal(&self) -> bool {
+        self.illegal_reason.is_none()
+    }
+}
+
+/// Every move, build, purchase and upgrade `empire` could consider with its
+/// own units and tiles, legal or not. Ending the turn is always possible and
+/// left out
+pub fn enumerate(
+    empire: i32,
+    world_state: &world_gen::WorldState,
+    tile_query: &Query<&tile::TileComponent>,
+    unit_query: &Query<&unit::Unit>,
+    inventory: &utils::Inventory,
+) -> Vec<ActionOption> {
+    let mut options = vec![];
+
+    let tile_at = |location: &utils::Coordinates| {
+        world_state
+            .tile_entities
+            .get(location)
+            .and_then(|tile_entity| tile_query.get(*tile_entity).ok())
+    };
+
+    let mut owned_buildings: Vec<_> = tile_query
+        .iter()
+        .filter(|tile| tile.owner == Some(empire) && tile.building.is_some())
+        .collect();
+    owned_buildings.sort_by_key(|tile| (tile.tile.location.x, tile.tile.location.y));
+
+    for tile in owned_buildings {
+        let location = Some(tile.tile.location);
+
+        for unit_kind in [
+            unit::UnitKind::Settler(default()),
+            unit::UnitKind::Caravan(default()),
+        ] {
+            let room = world_state
+                .occupancy
+                .can_enter(&tile.tile.location, &unit_kind);
+
+            options.push(ActionOption {
+                action: Action::BuyUnit(BuyUnit {
+                    unit_kind,
+                    location,
+                }),
+                cost: vec![],
+                illegal_reason: room.err(),
+            });
+        }
+
+        if let Some(cost) = tile.building.as_ref().and_then(building::upgrade_cost) {
+            options.push(ActionOption {
+                action: Action::Upgrade(Upgrade { location }),
+                illegal_reason: inventory.can_afford(&cost).err(),
+                cost,
+            });
+        }
+    }
+
+    let mut units: Vec<_> = unit_query
+        .iter()
+        .filter(|unit| unit.owner == Some(empire))
+        .collect();
+    units.sort_by_key(|unit| unit.id.0);
+
+    for unit in units {
+        let moved = unit
+            .moved
+            .then(|| "Unit already moved this turn".to_string());
+
+        if let (unit::UnitKind::Settler(_), Some(tile)) = (&unit.kind, tile_at(&unit.location)) {
+            options.push(ActionOption {
+                action: Action::FoundCity(unit.id),
+                cost: vec![],
+                illegal_reason: unit::settler::can_found_city(unit, tile, empire).err(),
+            });
+        }
+
+        for (x, y) in utils::DIRECTIONS.iter() {
+            let target = unit.location + utils::Coordinates { x: *x, y: *y };
+            let Some(tile) = tile_at(&target) else {
+                continue;
+            };
+
+            let reason = if !tile::is_land(&tile.tile.kind) {
+                Some("Can't walk on water".to_string())
+            } else if let Err(reason) = world_state.occupancy.can_enter(&target, &unit.kind) {
+                Some(reason)
+            } else {
+                moved.clone()
+            };
+
+            options.push(ActionOption {
+                action: Action::MoveUnit(MoveUnit {
+                    unit: unit.id,
+                    target,
+                }),
+                cost: vec![],
+                illegal_reason: reason,
+            });
+        }
+
+        options.push(ActionOption {
+            action: Action::KillUnit(unit.id),
+            cost: vec![],
+            illegal_reason: None,
+        });
+    }
+
+    options
+}
+
+/// The actions of `enumerate` that can be taken right now
+pub fn legal_actions(
+    empire: i32,
+    world_state: &world_gen::WorldState,
+    tile_query: &Query<&tile::TileComponent>,
+    unit_query: &Query<&unit::Unit>,
+    inventory: &utils::Inventory,
+) -> Vec<Action> {
+    enumerate(empire, world_state, tile_query, unit_query, inventory)
+        .into_iter()
+        .filter(ActionOption::is_legal)
+        .map(|option| option.action)
+        .collect()
+}
+
+/// Short description of an action for the UI
+pub fn describe(action: &Action) -> String {
+    match action {
+        Action::FoundCity(unit) => format!("Found city with unit {}", unit.0),
+        Action::KillUnit(unit) => format!("Disband unit {}", unit.0),
+        Action::BuyUnit(buy) => match buy.unit_kind {
+            unit::UnitKind::Settler(_) => "Buy settler".to_string(),
+            unit::UnitKind::Caravan(_) => "Buy caravan".to_string(),
+        },
+        Action::MoveUnit(move_unit) => format!(
+            "Move unit {} to ({}, {})",
+            move_unit.unit.0, move_unit.target.x, move_unit.target.y
+        ),
+        Action::Upgrade(_) => "Upgrade".to_string(),
+        action => format!("{:?}", action),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headless;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn capital_upgrade(app: &mut App) -> ActionOption {
+        app.world.run_system_once(
+            |world_state: Res<world_gen::WorldState>,
+             tile_query: Query<&tile::TileComponent>,
+             unit_query: Query<&unit::Unit>,
+             empire_query: Query<&crate::empire::Empire>| {
+                let empire = empire_query.get(world_state.empires[&0]).unwrap();
+
+                enumerate(0, &world_state, &tile_query, &unit_query, &empire.inventory)
+                    .into_iter()
+                    .find(|option| matches!(option.action, Action::Upgrade(_)))
+                    .unwrap()
+            },
+        )
+    }
+
+    #[test]
+    fn upgrades_need_resources() {
+        let mut app = headless::test_game(headless::test_settings(3));
+
+        let option = capital_upgrade(&mut app);
+        assert_eq!(option.illegal_reason, Some("Not enough Wood".to_string()));
+        assert!(!option.cost.is_empty());
+
+        for _ in 0..5 {
+            headless::send(&mut app, 0, Action::EndTurn);
+        }
+
+        let option = capital_upgrade(&mut app);
+        assert!(option.is_legal());
+    }
+
+    #[test]
+    fn settlers_found_cities_in_one_action() {
+        let mut app = headless::test_game(headless::test_settings(3));
+
+        let options = |app: &mut App| {
+            app.world.run_system_once(
+                |world_state: Res<world_gen::WorldState>,
+                 tile_query: Query<&tile::TileComponent>,
+                 unit_query: Query<&unit::Unit>,
+                 empire_query: Query<&crate::empire::Empire>| {
+                    let empire = empire_query.get(world_state.empires[&0]).unwrap();
+                    enumerate(0, &world_state, &tile_query, &unit_query, &empire.inventory)
+                },
+            )
+        };
+
+        // The starting settler stands on the capital
+        let (unit_id, reason) = options(&mut app)
+            .into_iter()
+            .find_map(|option| match option.action {
+                Action::FoundCity(unit_id) => Some((unit_id, option.illegal_reason)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(reason, Some("Can't build over a building".to_string()));
+
+        let step = options(&mut app)
+            .into_iter()
+            .find(|option| match &option.action {
+                Action::MoveUnit(MoveUnit { unit, target }) => {
+                    let kind = app.world.resource::<world_gen::WorldState>().tile_data[target].kind;
+                    *unit == unit_id && option.is_legal() && tile::is_settleable(&kind)
+                }
+                _ => false,
+            })
+            .unwrap();
+        headless::send(&mut app, 0, step.action);
+        assert!(options(&mut app).iter().any(|option| {
+            matches!(option.action, Action::FoundCity(id) if id == unit_id) && option.is_legal()
+        }));
+
+        let unit_entity = app.world.resource::<world_gen::WorldState>().units[&unit_id];
+        let location = app.world.get::<unit::Unit>(unit_entity).unwrap().location;
+
+        // Only the settler's owner can use it
+        for empire in [1, 0] {
+            headless::send(&mut app, empire, Action::FoundCity(unit_id));
+
+            let world_state = app.world.resource::<world_gen::WorldState>();
+            assert_eq!(world_state.units.contains_key(&unit_id), empire == 1);
+        }
+
+        let world_state = app.world.resource::<world_gen::WorldState>();
+        let tile_entity = world_state.tile_entities[&location];
+        assert!(world_state.occupancy.units_at(&location).is_empty());
+        let tile = app.world.get::<tile::TileComponent>(tile_entity).unwrap();
+        assert_eq!(tile.owner, Some(0));
+        assert!(matches!(tile.building, Some(building::Building::City(_))));
+    }
+}
@@ -1,96 +1,390 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::{building, controls, tick, tile, unit, utils, world_gen};
+use crate::{building, controls, empire, tick, tile, unit, utils, world_gen};
 
-#[derive(Clone, Component, Debug)]
+pub mod legal;
+
+#[derive(Clone, Component, Debug, Serialize, Deserialize)]
 pub enum Action {
-    Build(Build),
-    KillUnit(Entity),
+    /// Turns the settler into a city on the tile it stands on
+    FoundCity(unit::UnitId),
+    KillUnit(unit::UnitId),
     _Spawn(Spawn),
     BuyUnit(BuyUnit),
+    MoveUnit(MoveUnit),
+    SetTarget(SetTarget),
+    SetOrders(SetOrders),
+    Upgrade(Upgrade),
+    /// Takes back the acting empire's last action of this turn
+    Undo,
     Noop,
     EndTurn,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BuyUnit {
     pub unit_kind: unit::UnitKind,
+    /// Where to place the unit, `None` means the currently selected tile
+    pub location: Option<utils::Coordinates>,
+}
+
+/// Sets the unit's target and takes the first step towards it right away,
+/// queued orders are dropped
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MoveUnit {
+    pub unit: unit::UnitId,
+    pub target: utils::Coordinates,
+}
+
+/// Sets or clears the unit's target, it is walked towards at the end of turn.
+/// Queued orders are dropped
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetTarget {
+    pub unit: unit::UnitId,
+    pub target: Option<utils::Coordinates>,
+}
+
+/// Replaces the unit's order queue, or adds to its end
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetOrders {
+    pub unit: unit::UnitId,
+    pub orders: Vec<unit::orders::Order>,
+    pub append: bool,
+}
+
+/// Upgrades the building on a tile, paid from the empire's inventory
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Upgrade {
+    /// `None` means the currently selected tile
+    pub location: Option<utils::Coordinates>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Spawn {
     pub location: utils::Coordinates,
     pub unit: unit::Unit,
 }
 
-#[derive(Clone, Debug)]
-pub struct Build {
-    pub building: building::Building,
-    pub tile_entity: Entity,
-    pub owner: i32,
+/// Checks that `acting_empire` may issue the action, they can only command
+/// their own units, found cities on unowned or their own tiles and buy on
+/// their own tiles
+pub fn check_permission(
+    action: &Action,
+    acting_empire: i32,
+    tile_query: &Query<&mut tile::TileComponent>,
+    unit_query: &Query<(Entity, &Transform, &mut unit::Unit)>,
+    world_state: &world_gen::WorldState,
+) -> Result<(), String> {
+    let unit_owner = |unit_id: &unit::UnitId| {
+        world_state
+            .units
+            .get(unit_id)
+            .and_then(|unit_entity| unit_query.get(*unit_entity).ok())
+            .map(|(_, _, unit)| unit.owner)
+            .ok_or("Unit not found".to_string())
+    };
+    let tile_owner = |location: &utils::Coordinates| {
+        world_state
+            .tile_entities
+            .get(location)
+            .and_then(|tile_entity| tile_query.get(*tile_entity).ok())
+            .map(|tile| tile.owner)
+            .ok_or("Tile not found".to_string())
+    };
+
+    match action {
+        Action::FoundCity(unit_id) => {
+            let (_, _, unit) = world_state
+                .units
+                .get(unit_id)
+                .and_then(|unit_entity| unit_query.get(*unit_entity).ok())
+                .ok_or("Unit not found".to_string())?;
+            let tile = world_state
+                .tile_entities
+                .get(&unit.location)
+                .and_then(|tile_entity| tile_query.get(*tile_entity).ok())
+                .ok_or("Tile not found".to_string())?;
+            unit::settler::can_found_city(unit, tile, acting_empire)
+        }
+        Action::KillUnit(unit_id)
+        | Action::MoveUnit(MoveUnit { unit: unit_id, .. })
+        | Action::SetTarget(SetTarget { unit: unit_id, .. })
+        | Action::SetOrders(SetOrders { unit: unit_id, .. }) => {
+            if unit_owner(unit_id)? != Some(acting_empire) {
+                return Err("Unit belongs to another empire".to_string());
+            }
+            Ok(())
+        }
+        Action::BuyUnit(BuyUnit {
+            location: Some(location),
+            unit_kind,
+        }) => {
+            if tile_owner(location)? != Some(acting_empire) {
+                return Err("Units can only be bought on own tiles".to_string());
+            }
+            world_state.occupancy.can_enter(location, unit_kind)
+        }
+        Action::Upgrade(Upgrade {
+            location: Some(location),
+        }) => {
+            if tile_owner(location)? != Some(acting_empire) {
+                return Err("Only own buildings can be upgraded".to_string());
+            }
+            Ok(())
+        }
+        Action::BuyUnit(_)
+        | Action::Upgrade(_)
+        | Action::_Spawn(_)
+        | Action::Undo
+        | Action::Noop
+        | Action::EndTurn => Ok(()),
+    }
+}
+
+/// Checks everything `execute` would refuse, so servers can tell clients why
+/// an action was rejected instead of dropping it
+pub fn validate(
+    action: &Action,
+    acting_empire: i32,
+    tile_query: &Query<&mut tile::TileComponent>,
+    unit_query: &Query<(Entity, &Transform, &mut unit::Unit)>,
+    empire_query: &Query<&mut empire::Empire>,
+    world_state: &world_gen::WorldState,
+) -> Result<(), String> {
+    check_permission(action, acting_empire, tile_query, unit_query, world_state)?;
+
+    let on_map = |target: &utils::Coordinates| match world_state.tile_data.contains_key(target) {
+        true => Ok(()),
+        false => Err("Target is outside of the map".to_string()),
+    };
+
+    match action {
+        Action::MoveUnit(MoveUnit { target, .. })
+        | Action::SetTarget(SetTarget {
+            target: Some(target),
+            ..
+        }) => on_map(target),
+        Action::BuyUnit(BuyUnit { location: None, .. })
+        | Action::Upgrade(Upgrade { location: None }) => Err("No tile selected".to_string()),
+        Action::Upgrade(Upgrade {
+            location: Some(location),
+        }) => {
+            let cost = world_state
+                .tile_entities
+                .get(location)
+                .and_then(|tile_entity| tile_query.get(*tile_entity).ok())
+                .and_then(|tile| tile.building.as_ref().and_then(building::upgrade_cost))
+                .ok_or("Nothing to upgrade".to_string())?;
+            let empire = world_state
+                .empires
+                .get(&acting_empire)
+                .and_then(|empire_entity| empire_query.get(*empire_entity).ok())
+                .ok_or("Empire not found".to_string())?;
+            empire.inventory.can_afford(&cost)
+        }
+        Action::_Spawn(_) => Err("Not implemented".to_string()),
+        _ => Ok(()),
+    }
 }
 
-pub fn execute<'a, 'b, 'c, 'd, 'f, 'g, 'h>(
+pub fn execute<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i, 'j, 'k, 'l>(
     action: Action,
+    acting_empire: i32,
     mut tile_query: Query<'a, 'b, &'c mut tile::TileComponent>,
+    mut unit_query: Query<'i, 'j, (Entity, &'k Transform, &'l mut unit::Unit)>,
     mut selector_state: ResMut<'d, controls::SelectorState>,
     mut commands: Commands<'f, 'g>,
     mut end_turn_writer: EventWriter<'h, tick::EndTurnEvent>,
     building_resources: &Res<building::BuildingResources>,
     unit_resources: &Res<unit::UnitResources>,
-    world_state: &Res<world_gen::WorldState>,
+    world_state: &mut ResMut<world_gen::WorldState>,
+    empire_query: &mut Query<&mut empire::Empire>,
+    time: &Res<Time>,
 ) -> (
     Query<'a, 'b, &'c mut tile::TileComponent>,
+    Query<'i, 'j, (Entity, &'k Transform, &'l mut unit::Unit)>,
     ResMut<'d, controls::SelectorState>,
     Commands<'f, 'g>,
     EventWriter<'h, tick::EndTurnEvent>,
+    bool,
 ) {
     println!("Executing action: {:?}", action);
+    let mut accepted = true;
 
-    match action {
-        Action::Build(build) => {
-            let mut tile = tile_query.get_mut(build.tile_entity).unwrap();
+    if let Err(reason) = validate(
+        &action,
+        acting_empire,
+        &tile_query,
+        &unit_query,
+        empire_query,
+        &world_state,
+    ) {
+        println!("Rejected action: {}", reason);
 
-            tile.owner = Some(build.owner);
+        return (
+            tile_query,
+            unit_query,
+            selector_state,
+            commands,
+            end_turn_writer,
+            false,
+        );
+    }
 
-            tile.building = Some(build.building.clone());
+    match action {
+        Action::FoundCity(unit_id) => {
+            let unit_entity = *world_state.units.get(&unit_id).unwrap();
+            let unit = unit_query.get(unit_entity).unwrap().2.clone();
 
-            let building_bundle = building::make_bundle(&build.building, &building_resources);
-            let building_id = commands.spawn(building_bundle).id();
-            commands
-                .entity(build.tile_entity)
-                .push_children(&[building_id]);
+            if let Err(reason) = found_city(
+                &unit,
+                unit_entity,
+                acting_empire,
+                &mut tile_query,
+                &mut commands,
+                world_state,
+                &mut selector_state,
+                building_resources,
+            ) {
+                println!("Rejected action: {}", reason);
+                accepted = false;
+            }
         }
-        Action::KillUnit(unit_entity) => {
+        Action::KillUnit(unit_id) => {
             selector_state.selected_unit = None;
 
-            commands.entity(unit_entity).despawn();
+            if let Some(unit_entity) = world_state.units.remove(&unit_id) {
+                if let Ok((_, _, unit)) = unit_query.get(unit_entity) {
+                    world_state.occupancy.remove(unit_id, &unit.location);
+                }
+                commands.entity(unit_entity).despawn();
+            }
         }
         Action::_Spawn(spawn) => {
             let _unit_bundle =
                 unit::make_bundle(spawn.unit, &unit_resources, &world_state.tile_data);
             println!("Not implemented");
+            accepted = false;
         }
-        Action::BuyUnit(buy_action) => match selector_state.selected_tile {
-            Some(tile_entity) => {
-                let tile = tile_query.get(tile_entity).unwrap();
-
-                let location = tile.tile.location.clone();
+        Action::BuyUnit(buy_action) => match buy_action.location {
+            Some(location) => {
                 let unit = unit::Unit {
+                    id: world_state.next_unit_id(),
                     location,
-                    owner: Some(0),
+                    owner: Some(acting_empire),
                     target: None,
                     kind: buy_action.unit_kind,
                     moved: true,
+                    skip_next_move: false,
+                    orders: Vec::new(),
                 };
+                let unit_id = unit.id;
+                world_state.occupancy.add(&unit);
                 let unit_bundle = unit::make_bundle(unit, &unit_resources, &world_state.tile_data);
 
-                commands.spawn(unit_bundle);
+                let unit_entity = commands.spawn(unit_bundle).id();
+                world_state.units.insert(unit_id, unit_entity);
             }
             None => {
-                println!("No tile selected")
+                println!("No tile selected");
+                accepted = false;
             }
         },
+        Action::MoveUnit(move_unit) => {
+            if !world_state.tile_data.contains_key(&move_unit.target) {
+                println!("Target is outside of the map");
+                accepted = false;
+            } else if let Some(unit_entity) = world_state.units.get(&move_unit.unit).copied() {
+                let (unit_entity, unit_transform, mut unit) =
+                    unit_query.get_mut(unit_entity).unwrap();
+
+                unit.target = Some(move_unit.target);
+                unit.orders.clear();
+
+                let next_location = unit::next_location(&unit, &world_state);
+                unit.moved = true;
+                unit.skip_next_move =
+                    unit::crosses_river(world_state, &unit.location, &next_location);
+
+                (commands, _) = unit::next_location_update(
+                    commands,
+                    unit,
+                    time,
+                    unit_transform,
+                    &unit_entity,
+                    world_state,
+                    &next_location,
+                );
+            } else {
+                println!("Unit not found");
+                accepted = false;
+            }
+        }
+        Action::SetTarget(set_target) => {
+            let target_on_map = set_target
+                .target
+                .map_or(true, |target| world_state.tile_data.contains_key(&target));
+
+            if !target_on_map {
+                println!("Target is outside of the map");
+                accepted = false;
+            } else if let Some(unit_entity) = world_state.units.get(&set_target.unit) {
+                let (_, _, mut unit) = unit_query.get_mut(*unit_entity).unwrap();
+
+                unit.target = set_target.target;
+                unit.orders.clear();
+            } else {
+                println!("Unit not found");
+                accepted = false;
+            }
+        }
+        Action::SetOrders(set_orders) => {
+            if let Some(unit_entity) = world_state.units.get(&set_orders.unit) {
+                let (_, _, mut unit) = unit_query.get_mut(*unit_entity).unwrap();
+
+                if !set_orders.append {
+                    unit.orders.clear();
+                    unit.target = None;
+                }
+                unit.orders.extend(set_orders.orders);
+            } else {
+                println!("Unit not found");
+                accepted = false;
+            }
+        }
+        Action::Upgrade(upgrade) => match upgrade.location {
+            Some(location) => {
+                let tile_entity = *world_state.tile_entities.get(&location).unwrap();
+                let mut tile = tile_query.get_mut(tile_entity).unwrap();
+                let empire_entity = *world_state.empires.get(&acting_empire).unwrap();
+                let mut empire = empire_query.get_mut(empire_entity).unwrap();
+
+                let cost = tile.building.as_ref().and_then(building::upgrade_cost);
+                match cost.map(|cost| empire.inventory.can_afford(&cost).map(|_| cost)) {
+                    Some(Ok(cost)) => {
+                        empire.inventory.spend(&cost);
+                        building::upgrade(tile.building.as_mut().unwrap());
+                    }
+                    Some(Err(reason)) => {
+                        println!("Rejected action: {}", reason);
+                        accepted = false;
+                    }
+                    None => {
+                        println!("Nothing to upgrade");
+                        accepted = false;
+                    }
+                }
+            }
+            None => {
+                println!("No tile selected");
+                accepted = false;
+            }
+        },
+        Action::Undo => {
+            println!("Undo is handled by tick::execute_actions");
+            accepted = false;
+        }
         Action::Noop => {
             println!("Noop")
         }
@@ -99,5 +393,82 @@ pub fn execute<'a, 'b, 'c, 'd, 'f, 'g, 'h>(
         }
     }
 
-    (tile_query, selector_state, commands, end_turn_writer)
+    (
+        tile_query,
+        unit_query,
+        selector_state,
+        commands,
+        end_turn_writer,
+        accepted,
+    )
+}
+
+/// Turns `empire`'s settler into a city on the tile it stands on, the settler
+/// is used up
+pub fn found_city(
+    unit: &unit::Unit,
+    unit_entity: Entity,
+    empire: i32,
+    tile_query: &mut Query<&mut tile::TileComponent>,
+    commands: &mut Commands,
+    world_state: &mut world_gen::WorldState,
+    selector_state: &mut controls::SelectorState,
+    building_resources: &Res<building::BuildingResources>,
+) -> Result<(), String> {
+    let tile_entity = *world_state
+        .tile_entities
+        .get(&unit.location)
+        .ok_or("Tile not found".to_string())?;
+    let mut tile = tile_query
+        .get_mut(tile_entity)
+        .map_err(|_| "Tile not found".to_string())?;
+    unit::settler::can_found_city(unit, &tile, empire)?;
+
+    let building = building::Building::City(default());
+    let building_bundle = building::make_bundle(&building, building_resources);
+    let building_id = commands.spawn(building_bundle).id();
+    commands.entity(tile_entity).push_children(&[building_id]);
+    tile.owner = Some(empire);
+    tile.building = Some(building);
+
+    commands.entity(unit_entity).despawn();
+    world_state.units.remove(&unit.id);
+    world_state.occupancy.remove(unit.id, &unit.location);
+    if selector_state.selected_unit == Some(unit_entity) {
+        selector_state.selected_unit = None;
+    }
+
+    Ok(())
+}
+
+/// Fills in the parts of an action that depend on the current selection, so
+/// the action can be logged and replayed without the UI state
+pub fn resolve(
+    action: Action,
+    selector_state: &controls::SelectorState,
+    tile_query: &Query<&mut tile::TileComponent>,
+) -> Action {
+    match action {
+        Action::BuyUnit(BuyUnit {
+            unit_kind,
+            location: None,
+        }) => Action::BuyUnit(BuyUnit {
+            unit_kind,
+            location: selected_location(selector_state, tile_query),
+        }),
+        Action::Upgrade(Upgrade { location: None }) => Action::Upgrade(Upgrade {
+            location: selected_location(selector_state, tile_query),
+        }),
+        action => action,
+    }
+}
+
+fn selected_location(
+    selector_state: &controls::SelectorState,
+    tile_query: &Query<&mut tile::TileComponent>,
+) -> Option<utils::Coordinates> {
+    selector_state
+        .selected_tile
+        .and_then(|tile_entity| tile_query.get(tile_entity).ok())
+        .map(|tile| tile.tile.location)
 }
@@ -0,0 +1,210 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{tile, unit, utils};
+
+/// Lowercase letters are land, uppercase when the tile is owned. Rivers are
+/// `r`, cities are the digit of their empire (`*` above 9), settlers `@` and
+/// caravans `$`. North is up
+pub fn tile_char(kind: &tile::TileKind) -> char {
+    match kind {
+        tile::TileKind::Ocean => '~',
+        tile::TileKind::Shallows => '-',
+        tile::TileKind::Beach => 'b',
+        tile::TileKind::Desert => 'd',
+        tile::TileKind::Forest => 'f',
+        tile::TileKind::Mountain => 'm',
+        tile::TileKind::SnowyMountain => 'k',
+        tile::TileKind::Grassland => 'g',
+        tile::TileKind::Plains => 'p',
+        tile::TileKind::Tundra => 't',
+        tile::TileKind::Snow => 's',
+        tile::TileKind::Jungle => 'j',
+        tile::TileKind::Swamp => 'w',
+        tile::TileKind::Hills => 'h',
+    }
+}
+
+fn unit_char(kind: &unit::UnitKind) -> char {
+    match kind {
+        unit::UnitKind::Settler(_) => '@',
+        unit::UnitKind::Caravan(_) => '$',
+    }
+}
+
+/// Cities are drawn over units, units over terrain
+fn overlay_char(tile: &tile::TileComponent, unit: Option<&unit::Unit>) -> char {
+    if let (Some(_), Some(owner)) = (&tile.building, tile.owner) {
+        return match owner {
+            0..=9 => char::from_digit(owner as u32, 10).unwrap(),
+            _ => '*',
+        };
+    }
+    if let Some(unit) = unit {
+        return unit_char(&unit.kind);
+    }
+
+    let terrain = match tile.tile.river {
+        true => 'r',
+        false => tile_char(&tile.tile.kind),
+    };
+    match tile.owner {
+        Some(_) => terrain.to_ascii_uppercase(),
+        None => terrain,
+    }
+}
+
+/// One line per row of the map, missing tiles are spaces
+pub fn render<'a>(
+    tiles: impl Iterator<Item = &'a tile::TileComponent>,
+    units: impl Iterator<Item = &'a unit::Unit>,
+) -> String {
+    let tiles: HashMap<utils::Coordinates, &tile::TileComponent> =
+        tiles.map(|tile| (tile.tile.location, tile)).collect();
+
+    // The lowest id wins, so the dump does not depend on query order
+    let mut unit_at: HashMap<utils::Coordinates, &unit::Unit> = HashMap::new();
+    for unit in units {
+        let current = unit_at.entry(unit.location).or_insert(unit);
+        if unit.id.0 < current.id.0 {
+            *current = unit;
+        }
+    }
+
+    let width = tiles
+        .keys()
+        .map(|location| location.x + 1)
+        .max()
+        .unwrap_or(0);
+    let height = tiles
+        .keys()
+        .map(|location| location.y + 1)
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = vec![];
+    for y in (0..height).rev() {
+        let line: String = (0..width)
+            .map(|x| {
+                let location = utils::Coordinates { x, y };
+                tiles.get(&location).map_or(' ', |tile| {
+                    overlay_char(tile, unit_at.get(&location).copied())
+                })
+            })
+            .collect();
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+/// Dumps the tiles and units of a running game
+pub fn render_world(world: &mut World) -> String {
+    let tiles: Vec<tile::TileComponent> = world
+        .query::<&tile::TileComponent>()
+        .iter(world)
+        .cloned()
+        .collect();
+    let units: Vec<unit::Unit> = world.query::<&unit::Unit>().iter(world).cloned().collect();
+
+    render(tiles.iter(), units.iter())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{actions, building, config, headless};
+
+    #[test]
+    fn renders_terrain_owners_cities_and_units() {
+        let tile = |x: i32, y: i32, kind: tile::TileKind| tile::TileComponent {
+            owner: None,
+            building: None,
+            tile: tile::Tile {
+                location: utils::Coordinates { x, y },
+                kind,
+                height: 0.,
+                river: false,
+                body: 0,
+            },
+        };
+
+        let mut tiles = vec![
+            tile(0, 0, tile::TileKind::Ocean),
+            tile(1, 0, tile::TileKind::Beach),
+            tile(2, 0, tile::TileKind::Grassland),
+            tile(0, 1, tile::TileKind::Shallows),
+            tile(1, 1, tile::TileKind::Forest),
+            tile(2, 1, tile::TileKind::Hills),
+        ];
+        tiles[2].tile.river = true;
+        tiles[4].owner = Some(1);
+        tiles[5].owner = Some(1);
+        tiles[5].building = Some(building::Building::default());
+        let units = [
+            unit::Unit {
+                location: utils::Coordinates { x: 1, y: 0 },
+                ..default()
+            },
+            unit::Unit {
+                location: utils::Coordinates { x: 2, y: 1 },
+                ..default()
+            },
+        ];
+
+        assert_eq!(render(tiles.iter(), units.iter()), "-F1\n~@r");
+    }
+
+    #[test]
+    fn generated_worlds_show_every_capital() {
+        let mut app = headless::test_game(headless::test_settings(5));
+
+        let map = render_world(&mut app.world);
+        let lines: Vec<&str> = map.lines().collect();
+
+        assert_eq!(lines.len(), 60);
+        assert!(lines.iter().all(|line| line.len() == 60));
+        assert_eq!(map.matches('0').count(), 1);
+        assert_eq!(map.matches('1').count(), 1);
+    }
+
+    #[test]
+    fn seeded_game_matches_its_snapshot() {
+        let mut app = headless::test_game(config::GameSettings {
+            world_size: (20, 12),
+            ..headless::test_settings(5)
+        });
+
+        let snapshot = [
+            "~~~~--------b---bsss",
+            "~~~~------bbbbbbbsss",
+            "~~~-----bbbhhdtthhhh",
+            "~~-----bbdhhhhhhhmmh",
+            "-------bbdhhhhhhm0mh",
+            "--------bph1hhdhhmhd",
+            "-------bbphhddddhhbb",
+            "------bbbppbbbbbbbb-",
+            "--------bbbb----b---",
+            "--------------------",
+            "--------------------",
+            "-------------~~----~",
+        ];
+        assert_eq!(render_world(&mut app.world), snapshot.join("\n"));
+
+        // Empire 0's settler leaves its capital and walks for three turns
+        headless::send(
+            &mut app,
+            0,
+            actions::Action::SetTarget(actions::SetTarget {
+                unit: unit::UnitId(1),
+                target: Some(utils::Coordinates { x: 12, y: 4 }),
+            }),
+        );
+        for _ in 0..3 {
+            headless::send(&mut app, 0, actions::Action::EndTurn);
+        }
+
+        let mut snapshot = snapshot;
+        snapshot[5] = "--------bph1hh@hhmhd";
+        assert_eq!(render_world(&mut app.world), snapshot.join("\n"));
+    }
+}
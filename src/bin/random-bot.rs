@@ -0,0 +1,36 @@
+use std::io::BufReader;
+use std::net::TcpListener;
+
+use unciv::bot;
+
+/// Reference bot, plays one random legal action per turn. It talks over
+/// stdin and stdout, or waits for the game on `--listen <host:port>`
+fn main() {
+    let mut listen = None;
+    let mut seed = rand::random();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--listen", Some(address)) => listen = Some(address),
+            ("--seed", Some(value)) => seed = value.parse().unwrap_or(seed),
+            _ => eprintln!("Unknown argument: {}", arg),
+        }
+    }
+
+    let result = match listen {
+        Some(address) => TcpListener::bind(&address)
+            .and_then(|listener| listener.accept())
+            .and_then(|(stream, _)| {
+                let reader = BufReader::new(stream.try_clone()?);
+                bot::run_random_bot(reader, stream, seed)
+            }),
+        None => bot::run_random_bot(std::io::stdin().lock(), std::io::stdout().lock(), seed),
+    };
+
+    // stdout carries the protocol, so everything else goes to stderr
+    match result {
+        Ok(answered) => eprintln!("Answered {} observations", answered),
+        Err(error) => eprintln!("Bot stopped: {}", error),
+    }
+}
@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use unciv::{actions, ascii_map, config, headless, tick};
+
+/// Generates a world without a window and prints it as text, optionally
+/// after ending a few turns
+fn main() {
+    let (settings, turns) = settings_from_args();
+
+    let mut app = App::new();
+    headless::add_plugins(&mut app);
+    app.insert_resource(settings);
+    headless::add_new_game(&mut app);
+    headless::add_simulation(&mut app);
+    app.update();
+
+    for _ in 0..turns {
+        app.world.send_event(tick::ActionEvent {
+            action: actions::Action::EndTurn,
+            empire: 0,
+            source: tick::ActionSource::Local,
+        });
+        app.update();
+    }
+
+    println!("{}", ascii_map::render_world(&mut app.world));
+}
+
+/// `--seed <u32>`, `--empires <count>`, `--size <width>x<height>`,
+/// `--map-type <name>`, `--map <png>`, `--scenario <json>` and
+/// `--turns <count>`
+fn settings_from_args() -> (config::GameSettings, u32) {
+    let mut settings = config::GameSettings::default();
+    let mut turns = 0;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next();
+        let parsed = match (arg.as_str(), value.as_deref()) {
+            ("--seed", Some(seed)) => seed.parse().map(|seed| settings.seed = seed).is_ok(),
+            ("--empires", Some(empires)) => empires
+                .parse()
+                .map(|empires| settings.number_of_empires = empires)
+                .is_ok(),
+            ("--size", Some(size)) => match size.split_once('x') {
+                Some((width, height)) => match (width.parse(), height.parse()) {
+                    (Ok(width), Ok(height)) => {
+                        settings.world_size = (width, height);
+                        true
+                    }
+                    _ => false,
+                },
+                None => false,
+            },
+            ("--map-type", Some(name)) => config::MAP_TYPES
+                .into_iter()
+                .find(|map_type| format!("{:?}", map_type).eq_ignore_ascii_case(name))
+                .map(|map_type| settings.map_type = map_type)
+                .is_some(),
+            ("--map", Some(path)) => {
+                settings.starting_map = Some(path.to_string());
+                true
+            }
+            ("--scenario", Some(path)) => {
+                settings.scenario = Some(path.to_string());
+                true
+            }
+            ("--turns", Some(count)) => count.parse().map(|count| turns = count).is_ok(),
+            _ => false,
+        };
+
+        if !parsed {
+            println!("Unknown argument: {} {}", arg, value.unwrap_or_default());
+        }
+    }
+
+    (settings, turns)
+}
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use bevy::{app::ScheduleRunnerPlugin, prelude::*};
+use unciv::{config, server};
+
+/// Runs a game without a window, clients connect with `NetMessage::Join`
+/// and play by sending actions
+fn main() {
+    let (settings, turn_time) = settings_from_args();
+    println!(
+        "Hosting seed {} for {} players on {}",
+        settings.seed, settings.human_players, settings.address
+    );
+
+    let mut app = App::new();
+    if let Err(error) = server::build(&mut app, settings, turn_time) {
+        println!("Could not start server: {}", error);
+        return;
+    }
+    app.add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_millis(50)));
+
+    app.run();
+}
+
+/// `--address <host:port>`, `--seed <u32>`, `--players <count>`,
+/// `--empires <count>` and `--turn-seconds <seconds>`
+fn settings_from_args() -> (config::GameSettings, Option<Duration>) {
+    let mut settings = config::GameSettings {
+        human_players: 2,
+        ..default()
+    };
+    let mut turn_time = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next();
+        let parsed = match (arg.as_str(), value.as_deref()) {
+            ("--address", Some(address)) => {
+                settings.address = address.to_string();
+                true
+            }
+            ("--seed", Some(seed)) => seed.parse().map(|seed| settings.seed = seed).is_ok(),
+            ("--players", Some(players)) => players
+                .parse()
+                .map(|players| settings.human_players = players)
+                .is_ok(),
+            ("--empires", Some(empires)) => empires
+                .parse()
+                .map(|empires| settings.number_of_empires = empires)
+                .is_ok(),
+            ("--turn-seconds", Some(seconds)) => seconds
+                .parse()
+                .map(|seconds| turn_time = Some(Duration::from_secs_f32(seconds)))
+                .is_ok(),
+            _ => false,
+        };
+
+        if !parsed {
+            println!("Unknown argument: {} {}", arg, value.unwrap_or_default());
+        }
+    }
+
+    (settings, turn_time)
+}
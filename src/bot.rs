@@ -0,0 +1,435 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Mutex;
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{actions, building, config, empire, resource, tick, tile, unit, utils, world_gen};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ObservedTile {
+    pub location: utils::Coordinates,
+    pub kind: tile::TileKind,
+    pub river: bool,
+    pub owner: Option<i32>,
+    pub building: Option<building::Building>,
+}
+
+/// Sent to a bot as one JSON line at the start of every turn
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Observation {
+    pub turn: u32,
+    pub empire: i32,
+    pub tiles: Vec<ObservedTile>,
+    pub units: Vec<unit::Unit>,
+    pub inventory: Vec<(resource::Resource, i32)>,
+    pub legal_actions: Vec<actions::Action>,
+}
+
+/// A bot's answer to the observation of `turn`, sending it ends the bot's turn
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Reply {
+    pub turn: u32,
+    pub actions: Vec<actions::Action>,
+}
+
+/// Line based connection to a bot, either a child process or a socket
+pub struct BotLink {
+    pub empire: i32,
+    writer: Box<dyn Write + Send + Sync>,
+    lines: Mutex<Receiver<String>>,
+    child: Option<Child>,
+    socket: Option<TcpStream>,
+    observed_turn: Option<u32>,
+    /// The last turn the bot answered in time
+    pub replied_turn: Option<u32>,
+}
+
+impl BotLink {
+    pub fn spawn(empire: i32, command: &str) -> std::io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().unwrap_or_default();
+
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let mut link = Self::new(empire, Box::new(stdin), stdout);
+        link.child = Some(child);
+
+        Ok(link)
+    }
+
+    pub fn connect(empire: i32, address: &str) -> std::io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let reader = stream.try_clone()?;
+        let socket = stream.try_clone()?;
+
+        let mut link = Self::new(empire, Box::new(stream), reader);
+        link.socket = Some(socket);
+
+        Ok(link)
+    }
+
+    fn new(
+        empire: i32,
+        writer: Box<dyn Write + Send + Sync>,
+        reader: impl std::io::Read + Send + 'static,
+    ) -> Self {
+        // Reading blocks, so lines are handed over from a thread
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        BotLink {
+            empire,
+            writer,
+            lines: Mutex::new(lines),
+            child: None,
+            socket: None,
+            observed_turn: None,
+            replied_turn: None,
+        }
+    }
+
+    fn send(&mut self, observation: &Observation) -> std::io::Result<()> {
+        let line = serde_json::to_string(observation)?;
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()
+    }
+
+    /// Every reply received since the last poll
+    fn poll(&mut self) -> Vec<Reply> {
+        let lines = self.lines.get_mut().unwrap();
+
+        let mut replies = vec![];
+        loop {
+            match lines.try_recv() {
+                Ok(line) => match serde_json::from_str(&line) {
+                    Ok(reply) => replies.push(reply),
+                    Err(error) => println!("Bad reply from bot {}: {}", self.empire, error),
+                },
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+            }
+        }
+
+        replies
+    }
+}
+
+impl Drop for BotLink {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+        }
+        // Also stops the reading thread, which holds its own handle
+        if let Some(socket) = &self.socket {
+            let _ = socket.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Bots {
+    pub links: Vec<BotLink>,
+}
+
+/// Connects the bots of `config::GameSettings`, they play the empires after
+/// the human players
+pub fn start(mut commands: Commands, settings: Res<config::GameSettings>) {
+    let first_empire = settings.human_players.clamp(1, settings.number_of_empires);
+
+    let mut bots = Bots::default();
+    for (empire, spec) in (first_empire..settings.number_of_empires).zip(&settings.bots) {
+        let link = match spec {
+            config::BotSpec::Command(command) => BotLink::spawn(empire, command),
+            config::BotSpec::Address(address) => BotLink::connect(empire, address),
+        };
+
+        match link {
+            Ok(link) => bots.links.push(link),
+            Err(error) => println!("Could not start bot {:?}: {}", spec, error),
+        }
+    }
+
+    commands.insert_resource(bots);
+}
+
+pub fn stop(mut commands: Commands) {
+    commands.remove_resource::<Bots>();
+}
+
+pub fn observe(
+    empire: i32,
+    world_state: &world_gen::WorldState,
+    tile_query: &Query<&tile::TileComponent>,
+    unit_query: &Query<&unit::Unit>,
+    empire_query: &Query<&empire::Empire>,
+) -> Observation {
+    let mut units: Vec<unit::Unit> = unit_query
+        .iter()
+        .filter(|unit| unit.owner == Some(empire))
+        .cloned()
+        .collect();
+    units.sort_by_key(|unit| unit.id.0);
+
+    let empire_component = world_state
+        .empires
+        .get(&empire)
+        .and_then(|empire_entity| empire_query.get(*empire_entity).ok());
+
+    // Bots see what the game revealed to their empire, like a player would
+    let mut tiles: Vec<ObservedTile> = empire_component
+        .iter()
+        .flat_map(|empire| empire.explored.iter())
+        .filter_map(|location| world_state.tile_entities.get(location))
+        .filter_map(|tile_entity| tile_query.get(*tile_entity).ok())
+        .map(|tile| ObservedTile {
+            location: tile.tile.location,
+            kind: tile.tile.kind,
+            river: tile.tile.river,
+            owner: tile.owner,
+            building: tile.building.clone(),
+        })
+        .collect();
+    tiles.sort_by_key(|tile| (tile.location.x, tile.location.y));
+
+    let mut inventory: Vec<(resource::Resource, i32)> = empire_component
+        .map(|empire| empire.inventory.items.clone().into_iter().collect())
+        .unwrap_or_default();
+    inventory.sort();
+
+    let legal_actions = match empire_component {
+        Some(empire_component) => actions::legal::legal_actions(
+            empire,
+            world_state,
+            tile_query,
+            unit_query,
+            &empire_component.inventory,
+        ),
+        None => vec![],
+    };
+
+    Observation {
+        turn: world_state.turn,
+        empire,
+        tiles,
+        units,
+        inventory,
+        legal_actions,
+    }
+}
+
+/// Sends every bot its observation once per turn and executes their replies
+/// as their empire, the usual permission checks apply. In network games the
+/// replies are sent to the other peers like the local player's orders
+pub fn drive(
+    mut bots: ResMut<Bots>,
+    mut action_writer: EventWriter<tick::ActionEvent>,
+    world_state: Res<world_gen::WorldState>,
+    tile_query: Query<&tile::TileComponent>,
+    unit_query: Query<&unit::Unit>,
+    empire_query: Query<&empire::Empire>,
+) {
+    for link in bots.links.iter_mut() {
+        if link.observed_turn != Some(world_state.turn) {
+            let observation = observe(
+                link.empire,
+                &world_state,
+                &tile_query,
+                &unit_query,
+                &empire_query,
+            );
+            if let Err(error) = link.send(&observation) {
+                println!(
+                    "Could not send observation to bot {}: {}",
+                    link.empire, error
+                );
+            }
+            link.observed_turn = Some(world_state.turn);
+        }
+
+        for reply in link.poll() {
+            if reply.turn != world_state.turn {
+                println!("Bot {} replied late for turn {}", link.empire, reply.turn);
+                continue;
+            }
+            link.replied_turn = Some(reply.turn);
+
+            // Bots end their turn by replying, the world ticks with the humans
+            for action in reply.actions {
+                if let actions::Action::EndTurn = action {
+                    continue;
+                }
+                action_writer.send(tick::ActionEvent {
+                    action,
+                    empire: link.empire,
+                    source: tick::ActionSource::Bot,
+                });
+            }
+        }
+    }
+}
+
+/// The reference bot, it answers every observation with one random legal action
+pub fn random_reply(observation: &Observation, rng: &mut impl Rng) -> Reply {
+    let actions = observation
+        .legal_actions
+        .choose(rng)
+        .cloned()
+        .into_iter()
+        .collect();
+
+    Reply {
+        turn: observation.turn,
+        actions,
+    }
+}
+
+/// Runs the reference bot until the game closes the connection, returns the
+/// number of observations answered
+pub fn run_random_bot(
+    reader: impl BufRead,
+    mut writer: impl Write,
+    seed: u64,
+) -> std::io::Result<usize> {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let mut answered = 0;
+    for line in reader.lines() {
+        let observation: Observation = serde_json::from_str(&line?)?;
+        let reply = random_reply(&observation, &mut rng);
+
+        writeln!(writer, "{}", serde_json::to_string(&reply)?)?;
+        writer.flush()?;
+        answered += 1;
+    }
+
+    Ok(answered)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{headless, net};
+    use std::net::TcpListener;
+
+    /// Updates the game until the first bot answered `turn`
+    fn wait_for_reply(app: &mut App, turn: u32) {
+        net::test::wait_until("the bot's reply", || {
+            app.update();
+            app.world.resource::<Bots>().links[0].replied_turn == Some(turn)
+        });
+    }
+
+    #[test]
+    fn random_bot_plays_over_a_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let bot = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            run_random_bot(reader, stream, 5).unwrap()
+        });
+
+        let mut app = headless::test_app(config::GameSettings {
+            bots: vec![config::BotSpec::Address(address)],
+            ..headless::test_settings(5)
+        });
+
+        // The bot answers every turn before the player ends it
+        wait_for_reply(&mut app, 0);
+        for turn in 1..4 {
+            headless::send(&mut app, 0, actions::Action::EndTurn);
+            wait_for_reply(&mut app, turn);
+        }
+
+        let bot_units = app
+            .world
+            .query::<&unit::Unit>()
+            .iter(&app.world)
+            .filter(|unit| unit.owner == Some(1))
+            .count();
+        assert!(bot_units > 0);
+
+        app.world.remove_resource::<Bots>();
+        assert_eq!(bot.join().unwrap(), 4);
+    }
+
+    #[test]
+    fn bot_orders_go_through_lockstep() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let bot = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            run_random_bot(reader, stream, 7).unwrap()
+        });
+
+        // A second peer watches what the host sends
+        let mut host = net::NetSession::host("127.0.0.1:0").unwrap();
+        let host_address = match &host.role {
+            net::Role::Host { listener, .. } => listener.local_addr().unwrap().to_string(),
+            net::Role::Client { .. } => unreachable!(),
+        };
+        let mut peer = net::NetSession::join(&host_address).unwrap();
+        net::test::wait_for_clients(&mut host, 1);
+        host.lockstep = Some(net::Lockstep::new(0, vec![0]));
+
+        let mut app = headless::test_app(config::GameSettings {
+            bots: vec![config::BotSpec::Address(address)],
+            ..headless::test_settings(7)
+        });
+        app.insert_resource(host);
+        app.add_systems(Update, net::receive_turns.before(drive));
+
+        wait_for_reply(&mut app, 0);
+        headless::send(&mut app, 0, actions::Action::EndTurn);
+        net::test::wait_until("the next turn", || {
+            app.update();
+            app.world.resource::<world_gen::WorldState>().turn == 1
+        });
+
+        // The host's own orders come after the bot's
+        let mut bot_orders = vec![];
+        net::test::wait_until("the host's orders", || {
+            for message in peer.poll() {
+                match message {
+                    net::NetMessage::TurnActions {
+                        turn: 0,
+                        empire: 1,
+                        actions,
+                        ..
+                    } => bot_orders.push(actions),
+                    net::NetMessage::TurnActions {
+                        turn: 0, empire: 0, ..
+                    } => return true,
+                    _ => (),
+                }
+            }
+            false
+        });
+        assert_eq!(bot_orders.len(), 1);
+        assert_eq!(bot_orders[0].len(), 1);
+
+        app.world.remove_resource::<Bots>();
+        bot.join().unwrap();
+    }
+}
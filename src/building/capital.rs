@@ -1,10 +1,11 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{resource, tile};
 
 use super::BuildingTrait;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Capital {
     level: i32,
 }
@@ -27,6 +28,17 @@ impl BuildingTrait for Capital {
         format!("Capital {}", self.level)
     }
 
+    fn upgrade_cost(&self) -> Option<Vec<(resource::Resource, i32)>> {
+        Some(vec![
+            (resource::Resource::Wood, 5 * self.level),
+            (resource::Resource::Stone, 5 * self.level),
+        ])
+    }
+
+    fn upgrade(&mut self) {
+        self.level += 1;
+    }
+
     fn get_material(
         &self,
         building_resources: &Res<super::BuildingResources>,
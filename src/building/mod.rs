@@ -1,12 +1,13 @@
 use bevy::prelude::*;
 use bevy_mod_picking::PickableBundle;
+use serde::{Deserialize, Serialize};
 
 use crate::{resource, tile::TILE_SIZE};
 
 pub mod capital;
 pub mod house;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Building {
     Capital(capital::Capital),
     City(house::House),
@@ -26,6 +27,11 @@ trait BuildingTrait {
         -> Handle<StandardMaterial>;
     fn load_mesh() -> Mesh;
     fn load_material() -> StandardMaterial;
+    /// `None` if the building can't be upgraded any further
+    fn upgrade_cost(&self) -> Option<Vec<(resource::Resource, i32)>> {
+        None
+    }
+    fn upgrade(&mut self) {}
 }
 
 #[derive(Resource)]
@@ -62,6 +68,20 @@ pub fn building_production(building: &Building) -> Vec<(resource::Resource, i32)
     }
 }
 
+pub fn upgrade_cost(building: &Building) -> Option<Vec<(resource::Resource, i32)>> {
+    match building {
+        Building::Capital(capital) => capital.upgrade_cost(),
+        Building::City(city) => city.upgrade_cost(),
+    }
+}
+
+pub fn upgrade(building: &mut Building) {
+    match building {
+        Building::Capital(capital) => capital.upgrade(),
+        Building::City(city) => city.upgrade(),
+    }
+}
+
 pub fn building_name(building: &Building) -> String {
     match building {
         Building::Capital(capital) => capital.name(),
@@ -0,0 +1,192 @@
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{building, empire, replay, resource, tile, unit, utils, world_gen};
+
+/// The parts of the game state that actions and turns change, in a fixed
+/// order so equal states always serialize to the same bytes
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    turn: u32,
+    tiles: Vec<(
+        utils::Coordinates,
+        Option<i32>,
+        &'a Option<building::Building>,
+    )>,
+    units: Vec<&'a unit::Unit>,
+    inventories: Vec<(i32, Vec<(resource::Resource, i32)>)>,
+}
+
+/// Hash of the tiles with their owner and building, the units and the empire
+/// inventories. It is FNV-1a over the serialized state, so unlike
+/// `DefaultHasher` it stays the same across builds and machines
+pub fn state_hash<'a>(
+    world_state: &world_gen::WorldState,
+    tiles: impl Iterator<Item = &'a tile::TileComponent>,
+    units: impl Iterator<Item = &'a unit::Unit>,
+    empires: impl Iterator<Item = &'a empire::Empire>,
+) -> u64 {
+    let mut tiles: Vec<_> = tiles
+        .filter(|tile| tile.owner.is_some() || tile.building.is_some())
+        .map(|tile| (tile.tile.location, tile.owner, &tile.building))
+        .collect();
+    tiles.sort_by_key(|(location, _, _)| (location.x, location.y));
+
+    let mut units: Vec<_> = units.collect();
+    units.sort_by_key(|unit| unit.id.0);
+
+    let mut inventories: Vec<_> = empires
+        .map(|empire| {
+            let mut items: Vec<_> = empire.inventory.items.clone().into_iter().collect();
+            items.sort();
+            (empire.id, items)
+        })
+        .collect();
+    inventories.sort_by_key(|(id, _)| *id);
+
+    let snapshot = Snapshot {
+        turn: world_state.turn,
+        tiles,
+        units,
+        inventories,
+    };
+
+    fnv1a(&serde_json::to_vec(&snapshot).unwrap())
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// The first turn at which two runs of a game disagree, `None` if they agree
+/// on every turn both reached
+pub fn first_divergence(a: &[(u32, u64)], b: &[(u32, u64)]) -> Option<u32> {
+    a.iter()
+        .filter_map(|(turn, hash)| {
+            let other = b.iter().find(|(other_turn, _)| other_turn == turn)?;
+            (other.1 != *hash).then_some(*turn)
+        })
+        .min()
+}
+
+/// State hash at the start of every turn of this game, and the hashes other
+/// runs reported for the same turns
+#[derive(Resource, Default)]
+pub struct StateHashes {
+    pub local: Vec<(u32, u64)>,
+    pub expected: Vec<(u32, u64)>,
+    pub diverged_at: Option<u32>,
+}
+
+impl StateHashes {
+    pub fn get(&self, turn: u32) -> Option<u64> {
+        self.local
+            .iter()
+            .find(|(local_turn, _)| *local_turn == turn)
+            .map(|(_, hash)| *hash)
+    }
+
+    /// Adds a hash computed by another run, e.g. a peer or a recorded replay
+    pub fn expect(&mut self, turn: u32, hash: u64) {
+        self.expected.push((turn, hash));
+        self.check();
+    }
+
+    fn record(&mut self, turn: u32, hash: u64) {
+        self.local.push((turn, hash));
+        self.check();
+    }
+
+    fn check(&mut self) {
+        let Some(turn) = first_divergence(&self.local, &self.expected) else {
+            return;
+        };
+
+        if self
+            .diverged_at
+            .map_or(true, |diverged_at| turn < diverged_at)
+        {
+            println!("Desync: game state diverged at turn {}", turn);
+            self.diverged_at = Some(turn);
+        }
+    }
+}
+
+/// A recorded replay is checked against the hashes stored with it
+pub fn init(mut commands: Commands, replay: Option<Res<replay::Replay>>) {
+    let mut state_hashes = StateHashes::default();
+    if let Some(replay) = replay {
+        state_hashes.expected = replay.state_hashes.clone();
+    }
+
+    commands.insert_resource(state_hashes);
+}
+
+/// Hashes the state once at the start of every turn, after `tick::tick_world`
+pub fn record(
+    mut state_hashes: ResMut<StateHashes>,
+    world_state: Res<world_gen::WorldState>,
+    tile_query: Query<&tile::TileComponent>,
+    unit_query: Query<&unit::Unit>,
+    empire_query: Query<&empire::Empire>,
+    mut action_log: Option<ResMut<replay::ActionLog>>,
+) {
+    let turn = world_state.turn;
+    if state_hashes.get(turn).is_some() {
+        return;
+    }
+
+    let hash = state_hash(
+        &world_state,
+        tile_query.iter(),
+        unit_query.iter(),
+        empire_query.iter(),
+    );
+    println!("Turn {} state hash {:016x}", turn, hash);
+
+    if let Some(action_log) = action_log.as_mut() {
+        action_log.record_state_hash(turn, hash);
+    }
+    state_hashes.record(turn, hash);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{actions, config, headless};
+
+    #[test]
+    fn finds_first_diverging_turn() {
+        let a = [(0, 1), (1, 2), (2, 3), (3, 4)];
+        let b = [(0, 1), (1, 2), (2, 5), (3, 6)];
+
+        assert_eq!(first_divergence(&a, &b), Some(2));
+        assert_eq!(first_divergence(&a, &a), None);
+        assert_eq!(first_divergence(&a, &b[..2]), None);
+    }
+
+    #[test]
+    fn same_seed_and_actions_give_the_same_hashes() {
+        let run = || {
+            let mut app = headless::test_game(config::GameSettings {
+                number_of_empires: 3,
+                ..headless::test_settings(3)
+            });
+            for _ in 0..3 {
+                headless::send(&mut app, 0, actions::Action::EndTurn);
+            }
+
+            app.world.resource::<StateHashes>().local.clone()
+        };
+
+        let first = run();
+        assert_eq!(first.len(), 4);
+        assert_eq!(first_divergence(&first, &run()), None);
+        assert_ne!(first[0].1, first[3].1);
+    }
+}
@@ -1,10 +1,30 @@
-use bevy::input::keyboard::KeyCode;
+use bevy::{input::keyboard::KeyCode, prelude::Resource};
+use serde::{Deserialize, Serialize};
 
 pub struct KeyBinds {
     pub quit: KeyCode,
     pub zoom_in: KeyCode,
     pub zoom_out: KeyCode,
     pub action: KeyCode,
+    pub pause: KeyCode,
+    pub undo: KeyCode,
+    /// Order keys add to the selected unit's queue
+    pub found_city: KeyCode,
+    pub patrol: KeyCode,
+    pub sentry: KeyCode,
+    pub explore: KeyCode,
+    pub clear_orders: KeyCode,
+    /// Writes the map as PNG images, see `map_image::export`
+    pub export_map: KeyCode,
+    /// Prints the map as text, see `ascii_map::render`
+    pub dump_map: KeyCode,
+    /// Switches between playing and the map editor
+    pub editor: KeyCode,
+    /// Editor keys cycle through the brushes, tile kinds and empires
+    pub editor_brush: KeyCode,
+    pub editor_kind: KeyCode,
+    pub editor_empire: KeyCode,
+    pub save_scenario: KeyCode,
 }
 
 pub struct CameraConfig {
@@ -20,6 +40,7 @@ pub struct Config {
     pub camera: CameraConfig,
     pub world_size: (i32, i32),
     pub keys: KeyBinds,
+    pub address: &'static str,
 }
 
 pub const fn default_config() -> Config {
@@ -38,8 +59,87 @@ pub const fn default_config() -> Config {
             zoom_in: KeyCode::Equal,
             zoom_out: KeyCode::Minus,
             action: KeyCode::Enter,
+            pause: KeyCode::Escape,
+            undo: KeyCode::KeyZ,
+            found_city: KeyCode::KeyF,
+            patrol: KeyCode::KeyP,
+            sentry: KeyCode::KeyT,
+            explore: KeyCode::KeyE,
+            clear_orders: KeyCode::Backspace,
+            export_map: KeyCode::F9,
+            dump_map: KeyCode::F8,
+            editor: KeyCode::F7,
+            editor_brush: KeyCode::Tab,
+            editor_kind: KeyCode::KeyK,
+            editor_empire: KeyCode::KeyO,
+            save_scenario: KeyCode::F6,
         },
+        address: "127.0.0.1:7878",
     }
 }
 
 pub static CONFIG: Config = default_config();
+
+/// Options chosen in the main menu before a new game is generated
+#[derive(Resource, Clone)]
+pub struct GameSettings {
+    pub seed: u32,
+    pub number_of_empires: i32,
+    /// Empires played by people taking turns on this machine
+    pub human_players: i32,
+    pub world_size: (i32, i32),
+    pub map_type: MapType,
+    /// PNG heightmap or terrain image used instead of a generated map
+    pub starting_map: Option<String>,
+    /// Scenario file played instead of a random setup, see `scenario::Scenario`
+    pub scenario: Option<String>,
+    /// Where network games are hosted or joined
+    pub address: String,
+    /// External programs playing the empires after the human players
+    pub bots: Vec<BotSpec>,
+}
+
+/// Shapes the height field of a new world
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum MapType {
+    /// A few large landmasses
+    #[default]
+    Continents,
+    /// Many small islands
+    Archipelago,
+    /// One landmass in the middle of the map
+    Pangaea,
+    /// Land around a sea in the middle of the map
+    InlandSea,
+}
+
+pub const MAP_TYPES: [MapType; 4] = [
+    MapType::Continents,
+    MapType::Archipelago,
+    MapType::Pangaea,
+    MapType::InlandSea,
+];
+
+#[derive(Clone, Debug)]
+pub enum BotSpec {
+    /// Started by the game, talks over its stdin and stdout
+    Command(String),
+    /// Already running, the game connects to it
+    Address(String),
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        GameSettings {
+            seed: rand::random(),
+            number_of_empires: 10,
+            human_players: 1,
+            world_size: CONFIG.world_size,
+            map_type: MapType::default(),
+            starting_map: None,
+            scenario: None,
+            address: CONFIG.address.to_string(),
+            bots: vec![],
+        }
+    }
+}
@@ -1,47 +1,41 @@
 use bevy::{input::mouse::MouseWheel, prelude::*};
 use bevy_mod_picking::prelude::*;
 
-use crate::{actions, building, config::CONFIG, tick, tile, unit, utils, world_gen};
+use crate::{
+    actions, ascii_map, config::CONFIG, map_image, replay, tick, tile, unit, utils, world_gen,
+};
 
 #[derive(Resource)]
 pub struct SelectorState {
     pub selected_unit: Option<Entity>,
     pub selected_tile: Option<Entity>,
     pub selected_empire: Option<Entity>,
+    /// The empire the local player controls, every command is issued as this empire
+    pub local_empire: i32,
 }
 
 pub fn init_state(mut commands: Commands, world_state: ResMut<world_gen::WorldState>) {
-    match world_state.empires.get(&0) {
-        Some(entity) => {
-            commands.insert_resource(SelectorState {
-                selected_unit: None,
-                selected_tile: None,
-                selected_empire: Some(*entity),
-            });
-        }
-        None => {
-            commands.insert_resource(SelectorState {
-                selected_unit: None,
-                selected_tile: None,
-                selected_empire: None,
-            });
-        }
-    }
+    let local_empire = 0;
+
+    commands.insert_resource(SelectorState {
+        selected_unit: None,
+        selected_tile: None,
+        selected_empire: world_state.empires.get(&local_empire).copied(),
+        local_empire,
+    });
 }
 
 pub fn handle_keyboard(
-    mut commands: Commands,
     mut camera: Query<&mut Transform, With<Camera3d>>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mut app_exit_events: ResMut<Events<bevy::app::AppExit>>,
     time: Res<Time>,
     world_state: Res<world_gen::WorldState>,
-    mut selector_state: ResMut<SelectorState>,
+    selector_state: Res<SelectorState>,
     unit_query: Query<&unit::Unit>,
-    mut tile_query: Query<&mut tile::TileComponent>,
-    building_resources: Res<building::BuildingResources>,
-    unit_resources: Res<unit::UnitResources>,
-    mut end_turn_writer: EventWriter<tick::EndTurnEvent>,
+    tile_query: Query<&tile::TileComponent>,
+    mut action_writer: EventWriter<tick::ActionEvent>,
+    replay: Option<Res<replay::Replay>>,
 ) {
     if keyboard_input.just_pressed(CONFIG.keys.quit) {
         app_exit_events.send(bevy::app::AppExit);
@@ -79,32 +73,100 @@ pub fn handle_keyboard(
         transform.translation.y += delta_y * time.delta_seconds() * z;
     }
 
-    if keyboard_input.just_pressed(CONFIG.keys.action) {
+    if keyboard_input.just_pressed(CONFIG.keys.dump_map) {
+        println!(
+            "{}",
+            ascii_map::render(tile_query.iter(), unit_query.iter())
+        );
+    }
+
+    if keyboard_input.just_pressed(CONFIG.keys.export_map) {
+        match map_image::export(tile_query.iter(), map_image::EXPORT_PREFIX) {
+            Ok(()) => println!("Exported map to {}.*.png", map_image::EXPORT_PREFIX),
+            Err(error) => println!("Could not export map: {}", error),
+        }
+    }
+
+    if keyboard_input.just_pressed(CONFIG.keys.undo) && replay.is_none() {
+        action_writer.send(tick::ActionEvent {
+            action: actions::Action::Undo,
+            empire: selector_state.local_empire,
+            source: tick::ActionSource::Local,
+        });
+    }
+
+    if let (Some(unit_entity), None) = (selector_state.selected_unit, &replay) {
+        let unit = unit_query.get(unit_entity).unwrap();
+
+        if let Some((orders, append)) = order_keys(&keyboard_input, unit) {
+            action_writer.send(tick::ActionEvent {
+                action: actions::Action::SetOrders(actions::SetOrders {
+                    unit: unit.id,
+                    orders,
+                    append,
+                }),
+                empire: selector_state.local_empire,
+                source: tick::ActionSource::Local,
+            });
+        }
+    }
+
+    if keyboard_input.just_pressed(CONFIG.keys.action) && replay.is_none() {
         println!("Action key pressed");
         if let Some(unit_entity) = selector_state.selected_unit {
             let unit = unit_query.get(unit_entity).unwrap();
 
             let tile_entity = world_state.tile_entities.get(&unit.location).unwrap();
-            let tile = tile_query.get_mut(*tile_entity).unwrap();
+            let tile = tile_query.get(*tile_entity).unwrap();
 
-            let actions = unit::tile_action(unit, tile, unit_entity, *tile_entity, 0);
+            let actions = unit::tile_action(unit, tile, selector_state.local_empire);
 
             for action in actions {
-                (tile_query, selector_state, commands, end_turn_writer) = actions::execute(
+                action_writer.send(tick::ActionEvent {
                     action,
-                    tile_query,
-                    selector_state,
-                    commands,
-                    end_turn_writer,
-                    &building_resources,
-                    &unit_resources,
-                    &world_state,
-                );
+                    empire: selector_state.local_empire,
+                    source: tick::ActionSource::Local,
+                });
             }
         }
     }
 }
 
+/// The orders for the order key pressed this frame, and if they are added to
+/// the queue rather than replacing it
+fn order_keys(
+    keyboard_input: &ButtonInput<KeyCode>,
+    unit: &unit::Unit,
+) -> Option<(Vec<unit::orders::Order>, bool)> {
+    if keyboard_input.just_pressed(CONFIG.keys.found_city) {
+        return Some((vec![unit::orders::Order::FoundCity], true));
+    }
+    if keyboard_input.just_pressed(CONFIG.keys.sentry) {
+        return Some((vec![unit::orders::Order::Sentry], true));
+    }
+    if keyboard_input.just_pressed(CONFIG.keys.explore) {
+        return Some((vec![unit::orders::Order::Explore], false));
+    }
+    if keyboard_input.just_pressed(CONFIG.keys.clear_orders) {
+        return Some((vec![], false));
+    }
+    if keyboard_input.just_pressed(CONFIG.keys.patrol) {
+        // Patrols between the unit and where it was last sent
+        let last_move = unit.orders.iter().rev().find_map(|order| match order {
+            unit::orders::Order::MoveTo(target) => Some(*target),
+            _ => None,
+        });
+        match last_move.or(unit.target) {
+            Some(end) => {
+                return Some((vec![unit::orders::Order::Patrol(unit.location, end)], false))
+            }
+            None => println!("Give the unit a target to patrol to first"),
+        }
+    }
+
+    None
+}
+
 pub fn move_camera_to(mut camera: Query<&mut Transform, With<Camera3d>>, target: Transform) {
     for mut transform in camera.iter_mut() {
         transform.translation = target.translation;
@@ -117,11 +179,12 @@ pub fn update_selection(
     mut ev_inspect: EventReader<InspectTileEvent>,
     mut unit_inspect: EventReader<SelectUnit>,
     mut selector_state: ResMut<SelectorState>,
-    mut unit_query: Query<(Entity, &Transform, &mut unit::Unit)>,
-    time: Res<Time>,
+    unit_query: Query<&unit::Unit>,
     tile_query: Query<&tile::TileComponent>,
     world_state: Res<world_gen::WorldState>,
     unit_resources: Res<unit::UnitResources>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut action_writer: EventWriter<tick::ActionEvent>,
 ) {
     for ev in ev_inspect.read() {
         let tile = tile_query.get(ev.0);
@@ -134,26 +197,43 @@ pub fn update_selection(
                     selector_state.selected_empire = Some(*empire_entity);
                 }
 
-                if let Some(unit) = selector_state.selected_unit {
-                    let (unit_entity, unit_transform, mut unit) = unit_query.get_mut(unit).unwrap();
-
-                    unit.target = Some(tile.tile.location);
-
-                    let next_location = unit::next_location(&unit, &world_state);
-                    unit.moved = true;
+                let queue_move = keyboard_input.pressed(KeyCode::AltLeft)
+                    || keyboard_input.pressed(KeyCode::AltRight);
 
-                    (commands, _) = unit::next_location_update(
-                        commands,
-                        unit,
-                        &time,
-                        unit_transform,
-                        &unit_entity,
-                        &world_state,
-                        &next_location,
-                    );
+                if let Some(unit) = selector_state.selected_unit {
+                    let unit = unit_query.get(unit).unwrap();
+
+                    // Holding alt adds the move to the unit's orders, holding shift only sets
+                    // the target, the unit starts walking at end of turn
+                    let action = if queue_move {
+                        actions::Action::SetOrders(actions::SetOrders {
+                            unit: unit.id,
+                            orders: vec![unit::orders::Order::MoveTo(tile.tile.location)],
+                            append: true,
+                        })
+                    } else if keyboard_input.pressed(KeyCode::ShiftLeft)
+                        || keyboard_input.pressed(KeyCode::ShiftRight)
+                    {
+                        actions::Action::SetTarget(actions::SetTarget {
+                            unit: unit.id,
+                            target: Some(tile.tile.location),
+                        })
+                    } else {
+                        actions::Action::MoveUnit(actions::MoveUnit {
+                            unit: unit.id,
+                            target: tile.tile.location,
+                        })
+                    };
+
+                    action_writer.send(tick::ActionEvent {
+                        action,
+                        empire: selector_state.local_empire,
+                        source: tick::ActionSource::Local,
+                    });
                 }
 
-                if let Some(unit_enity) = selector_state.selected_unit {
+                // Queued moves keep the unit selected to chain more of them
+                if let (Some(unit_enity), false) = (selector_state.selected_unit, queue_move) {
                     (commands, selector_state) = deselect_unit(
                         commands,
                         selector_state,
@@ -183,6 +263,14 @@ pub fn update_selection(
 
         let unit_entity = ev.unit;
 
+        let Ok(unit) = unit_query.get(unit_entity) else {
+            continue;
+        };
+        if unit.owner != Some(selector_state.local_empire) {
+            println!("Can't select a unit of another empire");
+            continue;
+        }
+
         (commands, selector_state) = select_unit(
             commands,
             selector_state,
@@ -196,11 +284,11 @@ pub fn update_selection(
 fn deselect_unit<'a, 'b, 'c>(
     mut commands: Commands<'a, 'b>,
     mut selector_state: ResMut<'c, SelectorState>,
-    unit_query: &Query<(Entity, &Transform, &mut unit::Unit)>,
+    unit_query: &Query<&unit::Unit>,
     unit_entity: Entity,
     unit_resources: &Res<unit::UnitResources>,
 ) -> (Commands<'a, 'b>, ResMut<'c, SelectorState>) {
-    let (_, _, unit) = unit_query.get(unit_entity).unwrap();
+    let unit = unit_query.get(unit_entity).unwrap();
 
     commands
         .entity(unit_entity)
@@ -214,11 +302,11 @@ fn deselect_unit<'a, 'b, 'c>(
 fn select_unit<'a, 'b, 'c>(
     mut commands: Commands<'a, 'b>,
     mut selector_state: ResMut<'c, SelectorState>,
-    unit_query: &Query<(Entity, &Transform, &mut unit::Unit)>,
+    unit_query: &Query<&unit::Unit>,
     unit_entity: Entity,
     unit_resources: &Res<unit::UnitResources>,
 ) -> (Commands<'a, 'b>, ResMut<'c, SelectorState>) {
-    let (_, _, unit) = unit_query.get(unit_entity).unwrap();
+    let unit = unit_query.get(unit_entity).unwrap();
 
     commands
         .entity(unit_entity)
@@ -230,7 +318,7 @@ fn select_unit<'a, 'b, 'c>(
 }
 
 #[derive(Event)]
-pub struct InspectTileEvent(Entity);
+pub struct InspectTileEvent(pub Entity);
 
 impl From<ListenerInput<Pointer<Click>>> for InspectTileEvent {
     fn from(event: ListenerInput<Pointer<Click>>) -> Self {
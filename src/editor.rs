@@ -0,0 +1,425 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::config::CONFIG;
+use crate::{
+    building, checksum, config, controls, empire, geography, net, replay, scenario, state, tile,
+    undo, unit, utils, world_gen,
+};
+
+/// Height one click of `Brush::Raise` or `Brush::Lower` adds or removes
+pub const HEIGHT_STEP: f32 = tile::TILE_SIZE * 0.2;
+
+/// What clicking a tile does in the editor
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Brush {
+    Terrain,
+    Raise,
+    Lower,
+    /// Gives the tile to the brush's empire, or takes it away again
+    Owner,
+    City,
+    Capital,
+    Settler,
+    Caravan,
+    /// Removes the owner, building and units of the tile
+    Erase,
+}
+
+pub const BRUSHES: [Brush; 9] = [
+    Brush::Terrain,
+    Brush::Raise,
+    Brush::Lower,
+    Brush::Owner,
+    Brush::City,
+    Brush::Capital,
+    Brush::Settler,
+    Brush::Caravan,
+    Brush::Erase,
+];
+
+#[derive(Resource)]
+pub struct Editor {
+    pub brush: Brush,
+    /// Painted by `Brush::Terrain`
+    pub kind: tile::TileKind,
+    /// Owns what the brush places
+    pub empire: i32,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Editor {
+            brush: Brush::Terrain,
+            kind: tile::TileKind::Grassland,
+            empire: 0,
+        }
+    }
+}
+
+/// Switches between playing and editing, replays and network games have to
+/// stay in sync so they can't be edited
+pub fn toggle(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<state::AppState>>,
+    mut next_state: ResMut<NextState<state::AppState>>,
+    replay: Option<Res<replay::Replay>>,
+    net_session: Option<Res<net::NetSession>>,
+) {
+    if !keyboard_input.just_pressed(CONFIG.keys.editor) {
+        return;
+    }
+
+    match state.get() {
+        state::AppState::InGame if replay.is_none() && net_session.is_none() => {
+            next_state.set(state::AppState::Editor)
+        }
+        state::AppState::InGame => println!("Replays and network games can't be edited"),
+        state::AppState::Editor => next_state.set(state::AppState::InGame),
+        _ => (),
+    }
+}
+
+/// Edits are no actions, so the replay being recorded stops matching the game
+pub fn init(mut commands: Commands, action_log: Option<Res<replay::ActionLog>>) {
+    commands.init_resource::<Editor>();
+
+    if action_log.is_some() {
+        println!("Edited games are no longer recorded");
+        commands.remove_resource::<replay::ActionLog>();
+    }
+}
+
+/// Landmasses may have changed and undoing actions from before the edits
+/// would throw them away. The edited state is hashed anew
+pub fn finish(
+    mut commands: Commands,
+    mut tile_query: Query<&mut tile::TileComponent>,
+    mut world_state: ResMut<world_gen::WorldState>,
+    undo_stack: Option<ResMut<undo::UndoStack>>,
+) {
+    let mut tile_data: Vec<tile::TileComponent> = tile_query.iter().cloned().collect();
+    world_state.geography = geography::analyze(&mut tile_data);
+
+    for edited in tile_data {
+        let tile_entity = *world_state
+            .tile_entities
+            .get(&edited.tile.location)
+            .unwrap();
+        tile_query.get_mut(tile_entity).unwrap().tile.body = edited.tile.body;
+        world_state
+            .tile_data
+            .insert(edited.tile.location, edited.tile);
+    }
+
+    if let Some(mut undo_stack) = undo_stack {
+        undo_stack.clear();
+    }
+    commands.insert_resource(checksum::StateHashes::default());
+}
+
+pub fn handle_keyboard(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<Editor>,
+    world_state: Res<world_gen::WorldState>,
+    tile_query: Query<&tile::TileComponent>,
+    unit_query: Query<&unit::Unit>,
+    empire_query: Query<&empire::Empire>,
+    settings: Res<config::GameSettings>,
+) {
+    if keyboard_input.just_pressed(CONFIG.keys.editor_brush) {
+        editor.brush = utils::next_in_cycle(&BRUSHES, editor.brush);
+    }
+    if keyboard_input.just_pressed(CONFIG.keys.editor_kind) {
+        editor.kind = utils::next_in_cycle(&tile::TILE_KINDS, editor.kind);
+    }
+    if keyboard_input.just_pressed(CONFIG.keys.editor_empire) {
+        editor.empire = (editor.empire + 1) % world_state.empires.len().max(1) as i32;
+    }
+
+    if keyboard_input.just_pressed(CONFIG.keys.save_scenario) {
+        let scenario = scenario::from_world(
+            tile_query.iter(),
+            unit_query.iter(),
+            empire_query.iter(),
+            settings.seed,
+        );
+        match scenario::save(scenario::SCENARIO_PATH, &scenario) {
+            Ok(()) => println!("Saved scenario to {}", scenario::SCENARIO_PATH),
+            Err(error) => println!("Could not save scenario: {}", error),
+        }
+    }
+}
+
+/// Applies the brush to every clicked tile, the tile's material, height and
+/// the units standing on it follow right away
+pub fn paint(
+    mut commands: Commands,
+    mut ev_inspect: EventReader<controls::InspectTileEvent>,
+    editor: Res<Editor>,
+    mut tile_query: Query<(&mut tile::TileComponent, &mut Transform)>,
+    mut unit_query: Query<(&unit::Unit, &mut Transform), Without<tile::TileComponent>>,
+    mut world_state: ResMut<world_gen::WorldState>,
+    mut selector_state: ResMut<controls::SelectorState>,
+    tile_resources: Res<tile::TileResources>,
+    building_resources: Res<building::BuildingResources>,
+    unit_resources: Res<unit::UnitResources>,
+) {
+    for ev in ev_inspect.read() {
+        let tile_entity = ev.0;
+        let Ok((mut tile, mut transform)) = tile_query.get_mut(tile_entity) else {
+            println!("Tile not found");
+            continue;
+        };
+        let location = tile.tile.location;
+
+        match editor.brush {
+            Brush::Terrain => {
+                let occupied = tile.building.is_some()
+                    || !world_state.occupancy.units_at(&location).is_empty();
+                if !tile::is_land(&editor.kind) && occupied {
+                    println!("Erase the tile's units and buildings before flooding it");
+                    continue;
+                }
+
+                tile.tile.kind = editor.kind;
+                tile.tile.height = world_gen::tile_height(world_gen::typical_height(&editor.kind));
+                tile.tile.river &= tile::is_land(&editor.kind);
+            }
+            Brush::Raise => tile.tile.height += HEIGHT_STEP,
+            Brush::Lower => {
+                tile.tile.height =
+                    (tile.tile.height - HEIGHT_STEP).max(world_gen::tile_height(-1.));
+            }
+            Brush::Owner => {
+                tile.owner = match tile.owner == Some(editor.empire) {
+                    true => None,
+                    false => Some(editor.empire),
+                };
+            }
+            Brush::City | Brush::Capital => {
+                let building = match editor.brush {
+                    Brush::Capital => building::Building::Capital(default()),
+                    _ => building::Building::City(default()),
+                };
+
+                // Buildings are the only children of tiles
+                commands.entity(tile_entity).despawn_descendants();
+                let building_bundle = building::make_bundle(&building, &building_resources);
+                let building_id = commands.spawn(building_bundle).id();
+                commands.entity(tile_entity).push_children(&[building_id]);
+
+                tile.owner = Some(editor.empire);
+                tile.building = Some(building);
+            }
+            Brush::Settler | Brush::Caravan => {
+                let kind = match editor.brush {
+                    Brush::Caravan => unit::UnitKind::Caravan(default()),
+                    _ => unit::UnitKind::Settler(default()),
+                };
+                let setup = scenario::UnitSetup {
+                    kind,
+                    location,
+                    owner: editor.empire,
+                    orders: vec![],
+                };
+                if let Err(reason) =
+                    scenario::spawn_unit(setup, &mut commands, &mut world_state, &unit_resources)
+                {
+                    println!("Can't place unit: {}", reason);
+                    continue;
+                }
+            }
+            Brush::Erase => {
+                commands.entity(tile_entity).despawn_descendants();
+                tile.owner = None;
+                tile.building = None;
+
+                for unit_id in world_state.occupancy.units_at(&location) {
+                    world_state.occupancy.remove(unit_id, &location);
+                    if let Some(unit_entity) = world_state.units.remove(&unit_id) {
+                        commands.entity(unit_entity).despawn();
+                    }
+                }
+                selector_state.selected_unit = None;
+            }
+        }
+
+        transform.translation.z = tile.tile.height;
+        commands
+            .entity(tile_entity)
+            .insert(tile::material(&tile_resources, &tile));
+        world_state.tile_data.insert(location, tile.tile.clone());
+
+        for unit_id in world_state.occupancy.units_at(&location) {
+            let Some(unit_entity) = world_state.units.get(&unit_id) else {
+                continue;
+            };
+            if let Ok((unit, mut unit_transform)) = unit_query.get_mut(*unit_entity) {
+                unit_transform.translation.z = unit::unit_height(&world_state, &unit.location);
+            }
+        }
+
+        if editor.brush != Brush::Terrain {
+            continue;
+        }
+        for (x, y) in utils::DIRECTIONS.iter() {
+            let neighbor = location + utils::Coordinates { x: *x, y: *y };
+            let Some(kind) = shore_kind(&neighbor, &world_state.tile_data) else {
+                continue;
+            };
+            let tile_entity = *world_state.tile_entities.get(&neighbor).unwrap();
+            let (mut tile, _) = tile_query.get_mut(tile_entity).unwrap();
+
+            tile.tile.kind = kind;
+            commands
+                .entity(tile_entity)
+                .insert(tile::material(&tile_resources, &tile));
+            world_state.tile_data.insert(neighbor, tile.tile.clone());
+        }
+    }
+}
+
+/// The kind a land tile changes to after the water next to it was painted
+/// over: land on the shore is beach, beaches cut off from the water turn into
+/// grassland. `None` if the tile stays as it is
+fn shore_kind(
+    location: &utils::Coordinates,
+    tile_data: &HashMap<utils::Coordinates, tile::Tile>,
+) -> Option<tile::TileKind> {
+    let tile = tile_data.get(location)?;
+    if !tile::is_land(&tile.kind) {
+        return None;
+    }
+
+    let on_shore = utils::DIRECTIONS.iter().any(|(x, y)| {
+        tile_data
+            .get(&(*location + utils::Coordinates { x: *x, y: *y }))
+            .map_or(false, |neighbor| !tile::is_land(&neighbor.kind))
+    });
+    match (tile.kind == tile::TileKind::Beach, on_shore) {
+        (false, true) => Some(tile::TileKind::Beach),
+        (true, false) => Some(tile::TileKind::Grassland),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headless;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn click(app: &mut App, location: utils::Coordinates) {
+        let tile_entity = *app
+            .world
+            .resource::<world_gen::WorldState>()
+            .tile_entities
+            .get(&location)
+            .unwrap();
+        app.world
+            .send_event(controls::InspectTileEvent(tile_entity));
+        app.update();
+    }
+
+    #[test]
+    fn brushes_change_tiles_and_place_units() {
+        let mut app = headless::test_app(headless::test_settings(11));
+        app.add_systems(Update, paint.run_if(resource_exists::<Editor>));
+        app.update();
+        let hash_before = app.world.resource::<checksum::StateHashes>().get(0);
+        app.world.run_system_once(init);
+
+        let world_state = app.world.resource::<world_gen::WorldState>();
+        let mut locations: Vec<_> = world_state.tile_data.keys().copied().collect();
+        locations.sort_by_key(|location| (location.x, location.y));
+        let location = *locations
+            .iter()
+            .find(|location| world_state.occupancy.units_at(location).is_empty())
+            .unwrap();
+        let units_before = app.world.query::<&unit::Unit>().iter(&app.world).len();
+
+        app.world.resource_mut::<Editor>().kind = tile::TileKind::Plains;
+        click(&mut app, location);
+        app.world.resource_mut::<Editor>().brush = Brush::Capital;
+        app.world.resource_mut::<Editor>().empire = 1;
+        click(&mut app, location);
+        app.world.resource_mut::<Editor>().brush = Brush::Settler;
+        click(&mut app, location);
+        // The tile only has room for one civilian
+        click(&mut app, location);
+
+        let world_state = app.world.resource::<world_gen::WorldState>();
+        assert_eq!(
+            world_state.tile_data.get(&location).unwrap().kind,
+            tile::TileKind::Plains
+        );
+        assert_eq!(world_state.occupancy.units_at(&location).len(), 1);
+        let tile_entity = *world_state.tile_entities.get(&location).unwrap();
+        let tile = app.world.get::<tile::TileComponent>(tile_entity).unwrap();
+        assert_eq!(tile.owner, Some(1));
+        assert!(matches!(
+            tile.building,
+            Some(building::Building::Capital(_))
+        ));
+        assert_eq!(
+            app.world.query::<&unit::Unit>().iter(&app.world).len(),
+            units_before + 1
+        );
+
+        app.world.resource_mut::<Editor>().brush = Brush::Erase;
+        click(&mut app, location);
+
+        let tile = app.world.get::<tile::TileComponent>(tile_entity).unwrap();
+        assert_eq!(tile.owner, None);
+        assert!(tile.building.is_none());
+        assert_eq!(
+            app.world.query::<&unit::Unit>().iter(&app.world).len(),
+            units_before
+        );
+
+        // Occupied tiles can't be flooded, the shore around flooded ones
+        // turns into beach
+        let occupied = *app
+            .world
+            .resource::<world_gen::WorldState>()
+            .units
+            .keys()
+            .next()
+            .unwrap();
+        let occupied_entity = app.world.resource::<world_gen::WorldState>().units[&occupied];
+        let occupied_location = app
+            .world
+            .get::<unit::Unit>(occupied_entity)
+            .unwrap()
+            .location;
+        app.world.resource_mut::<Editor>().brush = Brush::Terrain;
+        app.world.resource_mut::<Editor>().kind = tile::TileKind::Ocean;
+        click(&mut app, occupied_location);
+        click(&mut app, location);
+
+        let world_state = app.world.resource::<world_gen::WorldState>();
+        assert!(tile::is_land(
+            &world_state.tile_data[&occupied_location].kind
+        ));
+        assert_eq!(world_state.tile_data[&location].kind, tile::TileKind::Ocean);
+        for (x, y) in utils::DIRECTIONS.iter() {
+            let neighbor = world_state
+                .tile_data
+                .get(&(location + utils::Coordinates { x: *x, y: *y }));
+            if let Some(neighbor) = neighbor.filter(|neighbor| tile::is_land(&neighbor.kind)) {
+                assert_eq!(neighbor.kind, tile::TileKind::Beach);
+            }
+        }
+
+        // The edited world is hashed again
+        app.world.resource_mut::<Editor>().brush = Brush::Owner;
+        click(&mut app, location);
+        app.world.run_system_once(finish);
+        app.update();
+        assert!(hash_before.is_some());
+        assert_ne!(
+            app.world.resource::<checksum::StateHashes>().get(0),
+            hash_before
+        );
+    }
+}
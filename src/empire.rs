@@ -1,12 +1,28 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashSet};
 
 use crate::utils;
 
+/// Tiles this close to an empire's units and tiles are explored
+pub const SIGHT_RANGE: i32 = 2;
+
 #[derive(Component)]
 pub struct Empire {
     pub id: i32,
+    pub name: String,
     pub color: Handle<ColorMaterial>,
     pub inventory: utils::Inventory,
+    /// Every tile the empire has seen, tiles are never forgotten
+    pub explored: HashSet<utils::Coordinates>,
+}
+
+impl Empire {
+    pub fn reveal(&mut self, center: utils::Coordinates) {
+        for x in -SIGHT_RANGE..=SIGHT_RANGE {
+            for y in -SIGHT_RANGE..=SIGHT_RANGE {
+                self.explored.insert(center + utils::Coordinates { x, y });
+            }
+        }
+    }
 }
 
 pub fn id_to_hue(empire_id: i32) -> f32 {
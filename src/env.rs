@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{actions, building, config, empire, headless, resource, tick, tile, unit, world_gen};
+
+/// The game state as seen by one empire, every grid holds one value per tile
+/// at index `y * width + x`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Observation {
+    pub turn: u32,
+    pub world_size: (i32, i32),
+    /// `tile::TileKind` as its discriminant
+    pub kind: Vec<u8>,
+    pub height: Vec<f32>,
+    /// Owning empire, -1 for unowned tiles
+    pub owner: Vec<i32>,
+    /// 0 for none, 1 for a capital, 2 for a city
+    pub building: Vec<u8>,
+    /// Empire of the unit on the tile, -1 for none
+    pub unit_owner: Vec<i32>,
+    /// 0 for none, 1 for a settler, 2 for a caravan
+    pub unit_kind: Vec<u8>,
+    pub inventory: Vec<(resource::Resource, i32)>,
+}
+
+impl Observation {
+    pub fn index(&self, x: i32, y: i32) -> usize {
+        (y * self.world_size.0 + x) as usize
+    }
+}
+
+/// Runs the simulation without a window for training agents. The agent plays
+/// `empire`, the other empires idle
+pub struct Env {
+    pub settings: config::GameSettings,
+    pub empire: i32,
+    /// The episode is done once this turn is reached
+    pub max_turns: u32,
+    app: App,
+}
+
+impl Env {
+    pub fn new(settings: config::GameSettings, max_turns: u32) -> Self {
+        let app = Self::build_app(&settings);
+
+        let mut env = Env {
+            settings,
+            empire: 0,
+            max_turns,
+            app,
+        };
+        env.app.update();
+
+        env
+    }
+
+    fn build_app(settings: &config::GameSettings) -> App {
+        let mut app = App::new();
+
+        headless::add_plugins(&mut app);
+        app.insert_resource(config::GameSettings {
+            human_players: 1,
+            ..settings.clone()
+        });
+        headless::add_new_game(&mut app);
+        headless::add_simulation(&mut app);
+
+        app
+    }
+
+    /// Starts a new episode on the world generated from `seed`
+    pub fn reset(&mut self, seed: u32) -> Observation {
+        self.settings.seed = seed;
+        self.app = Self::build_app(&self.settings);
+        self.app.update();
+
+        self.observe()
+    }
+
+    /// Executes the actions as the agent's empire and ends the turn, the
+    /// reward is the change of `score`
+    pub fn step(&mut self, actions: Vec<actions::Action>) -> (Observation, f32, bool) {
+        let score_before = self.score();
+
+        for action in actions.into_iter().chain([actions::Action::EndTurn]) {
+            self.app.world.send_event(tick::ActionEvent {
+                action,
+                empire: self.empire,
+                source: tick::ActionSource::Scripted,
+            });
+        }
+        self.app.update();
+
+        let reward = self.score() - score_before;
+        let turn = self.app.world.resource::<world_gen::WorldState>().turn;
+        let done = turn >= self.max_turns || self.owned_tiles() == 0;
+
+        (self.observe(), reward, done)
+    }
+
+    pub fn observe(&mut self) -> Observation {
+        // Scenarios and imported maps decide the size once the world spawned
+        let (width, height) = self.app.world.resource::<config::GameSettings>().world_size;
+        let size = (width * height) as usize;
+
+        let mut observation = Observation {
+            turn: self.app.world.resource::<world_gen::WorldState>().turn,
+            world_size: (width, height),
+            kind: vec![0; size],
+            height: vec![0.; size],
+            owner: vec![-1; size],
+            building: vec![0; size],
+            unit_owner: vec![-1; size],
+            unit_kind: vec![0; size],
+            inventory: self.inventory(),
+        };
+
+        let world = &mut self.app.world;
+        for tile in world.query::<&tile::TileComponent>().iter(world) {
+            let index = observation.index(tile.tile.location.x, tile.tile.location.y);
+
+            observation.kind[index] = tile.tile.kind as u8;
+            observation.height[index] = tile.tile.height;
+            observation.owner[index] = tile.owner.unwrap_or(-1);
+            observation.building[index] = match tile.building {
+                None => 0,
+                Some(building::Building::Capital(_)) => 1,
+                Some(building::Building::City(_)) => 2,
+            };
+        }
+        for unit in world.query::<&unit::Unit>().iter(world) {
+            let index = observation.index(unit.location.x, unit.location.y);
+
+            observation.unit_owner[index] = unit.owner.unwrap_or(-1);
+            observation.unit_kind[index] = match unit.kind {
+                unit::UnitKind::Settler(_) => 1,
+                unit::UnitKind::Caravan(_) => 2,
+            };
+        }
+
+        observation
+    }
+
+    /// Resources held plus ten for every building of the agent's empire
+    pub fn score(&mut self) -> f32 {
+        let resources: i32 = self.inventory().iter().map(|(_, amount)| amount).sum();
+
+        let world = &mut self.app.world;
+        let buildings = world
+            .query::<&tile::TileComponent>()
+            .iter(world)
+            .filter(|tile| tile.owner == Some(self.empire) && tile.building.is_some())
+            .count();
+
+        resources as f32 + 10. * buildings as f32
+    }
+
+    fn owned_tiles(&mut self) -> usize {
+        let world = &mut self.app.world;
+
+        world
+            .query::<&tile::TileComponent>()
+            .iter(world)
+            .filter(|tile| tile.owner == Some(self.empire))
+            .count()
+    }
+
+    fn inventory(&self) -> Vec<(resource::Resource, i32)> {
+        let world = &self.app.world;
+
+        let mut inventory: Vec<_> = world
+            .resource::<world_gen::WorldState>()
+            .empires
+            .get(&self.empire)
+            .and_then(|empire_entity| world.get::<empire::Empire>(*empire_entity))
+            .map(|empire| empire.inventory.items.clone().into_iter().collect())
+            .unwrap_or_default();
+        inventory.sort();
+
+        inventory
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn episodes_are_reproducible() {
+        let settings = config::GameSettings {
+            number_of_empires: 2,
+            world_size: (60, 60),
+            ..default()
+        };
+        let mut env = Env::new(settings, 3);
+
+        let first = env.reset(11);
+        let capital = (0..first.building.len())
+            .find(|index| first.building[*index] == 1 && first.owner[*index] == 0)
+            .unwrap();
+        let location = crate::utils::Coordinates {
+            x: capital as i32 % 60,
+            y: capital as i32 / 60,
+        };
+
+        let buy = actions::Action::BuyUnit(actions::BuyUnit {
+            unit_kind: unit::UnitKind::Settler(default()),
+            location: Some(location),
+        });
+        let (observation, _, done) = env.step(vec![buy.clone()]);
+        assert_eq!(observation.turn, 1);
+        assert_eq!(observation.unit_owner[capital], 0);
+        assert!(!done);
+
+        let (_, _, done) = env.step(vec![]);
+        assert!(!done);
+        let (last, _, done) = env.step(vec![]);
+        assert!(done);
+
+        assert_eq!(env.reset(11), first);
+        env.step(vec![buy]);
+        env.step(vec![]);
+        let (again, _, _) = env.step(vec![]);
+        assert_eq!(again, last);
+    }
+}
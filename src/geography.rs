@@ -0,0 +1,151 @@
+use bevy::utils::HashMap;
+
+use crate::{tile, utils};
+
+/// Water bodies up to this size are lakes, bigger ones are oceans
+pub const MAX_LAKE_SIZE: usize = 30;
+
+/// A connected landmass or water body
+#[derive(Clone, Debug, PartialEq)]
+pub struct Body {
+    pub land: bool,
+    pub size: usize,
+}
+
+/// The landmasses and water bodies of the map, `tile::Tile::body` indexes
+/// into `bodies`
+#[derive(Default)]
+pub struct Geography {
+    pub bodies: Vec<Body>,
+}
+
+impl Geography {
+    pub fn body(&self, tile: &tile::Tile) -> Option<&Body> {
+        self.bodies.get(tile.body as usize)
+    }
+
+    pub fn same_landmass(&self, a: &tile::Tile, b: &tile::Tile) -> bool {
+        a.body == b.body && tile::is_land(&a.kind)
+    }
+
+    /// Land tiles connected to the tile, 0 for water
+    pub fn landmass_size(&self, tile: &tile::Tile) -> usize {
+        match self.body(tile) {
+            Some(body) if body.land => body.size,
+            _ => 0,
+        }
+    }
+
+    pub fn is_lake(&self, tile: &tile::Tile) -> bool {
+        match self.body(tile) {
+            Some(body) => !body.land && body.size <= MAX_LAKE_SIZE,
+            None => false,
+        }
+    }
+}
+
+/// Splits the tiles into landmasses and water bodies, tiles touching along
+/// any of `utils::DIRECTIONS` belong together. Sets `tile::Tile::body` of
+/// every tile, ids only depend on the map
+pub fn analyze(tile_data: &mut [tile::TileComponent]) -> Geography {
+    let index: HashMap<utils::Coordinates, usize> = tile_data
+        .iter()
+        .enumerate()
+        .map(|(i, tile)| (tile.tile.location, i))
+        .collect();
+
+    let mut order: Vec<usize> = (0..tile_data.len()).collect();
+    order.sort_by_key(|i| (tile_data[*i].tile.location.x, tile_data[*i].tile.location.y));
+
+    let mut assigned = vec![false; tile_data.len()];
+    let mut geography = Geography::default();
+
+    for start in order {
+        if assigned[start] {
+            continue;
+        }
+
+        let body = geography.bodies.len() as u32;
+        let land = tile::is_land(&tile_data[start].tile.kind);
+        let mut frontier = vec![start];
+        assigned[start] = true;
+        let mut size = 0;
+
+        while let Some(current) = frontier.pop() {
+            tile_data[current].tile.body = body;
+            size += 1;
+
+            for (x, y) in utils::DIRECTIONS.iter() {
+                let location =
+                    tile_data[current].tile.location + utils::Coordinates { x: *x, y: *y };
+                let Some(&next) = index.get(&location) else {
+                    continue;
+                };
+
+                if !assigned[next] && tile::is_land(&tile_data[next].tile.kind) == land {
+                    assigned[next] = true;
+                    frontier.push(next);
+                }
+            }
+        }
+
+        geography.bodies.push(Body { land, size });
+    }
+
+    geography
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tile(x: i32, y: i32, kind: tile::TileKind) -> tile::TileComponent {
+        tile::TileComponent {
+            owner: None,
+            building: None,
+            tile: tile::Tile {
+                location: utils::Coordinates { x, y },
+                kind,
+                height: 0.,
+                river: false,
+                body: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn finds_islands_and_lakes() {
+        // Two islands split by a strait, the second island holds a lake
+        let map = ["##.###", "##.#.#", "##.###"];
+        let mut tile_data: Vec<_> = map
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.chars().enumerate().map(move |(x, c)| {
+                    let kind = match c {
+                        '#' => tile::TileKind::Grassland,
+                        _ => tile::TileKind::Shallows,
+                    };
+                    tile(x as i32, y as i32, kind)
+                })
+            })
+            .collect();
+
+        let geography = analyze(&mut tile_data);
+        let at = |x: i32, y: i32| {
+            tile_data
+                .iter()
+                .find(|tile| tile.tile.location == utils::Coordinates { x, y })
+                .unwrap()
+                .tile
+                .clone()
+        };
+
+        assert_eq!(geography.bodies.len(), 4);
+        assert!(geography.same_landmass(&at(0, 0), &at(1, 2)));
+        assert!(!geography.same_landmass(&at(0, 0), &at(3, 0)));
+        assert_eq!(geography.landmass_size(&at(5, 1)), 8);
+        assert_eq!(geography.landmass_size(&at(2, 1)), 0);
+        assert!(geography.is_lake(&at(4, 1)));
+    }
+}
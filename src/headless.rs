@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+
+#[cfg(test)]
+use crate::{actions, config};
+use crate::{bot, checksum, controls, hotseat, state, tick, triggers, undo, world_gen};
+
+/// Sets up an app that runs the simulation without a window or renderer, the
+/// render assets are still registered since the game entities carry handles
+pub fn add_plugins(app: &mut App) {
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), HierarchyPlugin))
+        .init_asset::<StandardMaterial>()
+        .init_asset::<Mesh>()
+        .init_asset::<Scene>()
+        .init_asset::<ColorMaterial>()
+        .init_state::<state::AppState>();
+
+    crate::add_events(app);
+}
+
+/// Generates a new world from the `config::GameSettings` resource on startup
+pub fn add_new_game(app: &mut App) {
+    app.add_systems(
+        Startup,
+        (
+            crate::add_resources,
+            world_gen::spawn,
+            hotseat::init,
+            checksum::init,
+            undo::init,
+            bot::start,
+            controls::init_state,
+            hotseat::init_view,
+        )
+            .chain(),
+    );
+}
+
+/// The systems that advance the game, without any input or rendering
+pub fn add_simulation(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            bot::drive.run_if(resource_exists::<bot::Bots>),
+            tick::execute_actions,
+            tick::tick_world,
+            triggers::evaluate,
+            checksum::record,
+        )
+            .chain(),
+    );
+}
+
+/// Two empires on a 60x60 map, what most tests play on
+#[cfg(test)]
+pub fn test_settings(seed: u32) -> config::GameSettings {
+    config::GameSettings {
+        seed,
+        number_of_empires: 2,
+        world_size: (60, 60),
+        ..default()
+    }
+}
+
+/// A headless game with the simulation, the world spawns on the first update
+#[cfg(test)]
+pub fn test_app(settings: config::GameSettings) -> App {
+    let mut app = App::new();
+    add_plugins(&mut app);
+    app.insert_resource(settings);
+    add_new_game(&mut app);
+    add_simulation(&mut app);
+    app
+}
+
+/// A `test_app` with its world already spawned
+#[cfg(test)]
+pub fn test_game(settings: config::GameSettings) -> App {
+    let mut app = test_app(settings);
+    app.update();
+    app
+}
+
+/// Issues `action` as `empire`'s local player and runs a frame
+#[cfg(test)]
+pub fn send(app: &mut App, empire: i32, action: actions::Action) {
+    app.world.send_event(tick::ActionEvent {
+        action,
+        empire,
+        source: tick::ActionSource::Local,
+    });
+    app.update();
+}
@@ -0,0 +1,167 @@
+use bevy::prelude::*;
+
+use crate::{building, config, controls, empire, tile, unit, utils, world_gen};
+
+/// The human controlled empires sharing this machine, in the order they play
+#[derive(Resource)]
+pub struct TurnOrder {
+    pub players: Vec<i32>,
+    pub current: usize,
+}
+
+impl TurnOrder {
+    pub fn current_player(&self) -> i32 {
+        self.players[self.current]
+    }
+
+    /// Passes the turn to the next player, returns true once every player has
+    /// ended their turn and the world should be ticked
+    pub fn end_turn(&mut self, empire: i32) -> Result<bool, String> {
+        if empire != self.current_player() {
+            return Err(format!("It is not empire {}'s turn", empire));
+        }
+
+        self.current = (self.current + 1) % self.players.len();
+
+        Ok(self.current == 0)
+    }
+}
+
+pub fn init(mut commands: Commands, settings: Res<config::GameSettings>) {
+    let human_players = settings
+        .human_players
+        .clamp(1, settings.number_of_empires.max(1));
+
+    commands.insert_resource(TurnOrder {
+        players: (0..human_players).collect(),
+        current: 0,
+    });
+}
+
+/// Shows only the tiles `player` explored and the rival units on them, the
+/// player's own units are always shown
+fn show_view(
+    player: i32,
+    world_state: &world_gen::WorldState,
+    empire_query: &Query<&empire::Empire>,
+    tile_query: &mut Query<(&tile::TileComponent, &mut Visibility)>,
+    unit_query: &mut Query<(&unit::Unit, &mut Visibility), Without<tile::TileComponent>>,
+) {
+    let Some(empire) = world_state
+        .empires
+        .get(&player)
+        .and_then(|empire_entity| empire_query.get(*empire_entity).ok())
+    else {
+        return;
+    };
+    let visibility = |shown: bool| match shown {
+        true => Visibility::Inherited,
+        false => Visibility::Hidden,
+    };
+
+    for (tile, mut tile_visibility) in tile_query.iter_mut() {
+        *tile_visibility = visibility(empire.explored.contains(&tile.tile.location));
+    }
+    for (unit, mut unit_visibility) in unit_query.iter_mut() {
+        let shown = unit.owner == Some(player) || empire.explored.contains(&unit.location);
+        *unit_visibility = visibility(shown);
+    }
+}
+
+/// Hotseat games start out showing only the first player's view
+pub fn init_view(
+    turn_order: Res<TurnOrder>,
+    world_state: Res<world_gen::WorldState>,
+    empire_query: Query<&empire::Empire>,
+    mut tile_query: Query<(&tile::TileComponent, &mut Visibility)>,
+    mut unit_query: Query<(&unit::Unit, &mut Visibility), Without<tile::TileComponent>>,
+) {
+    if turn_order.players.len() > 1 {
+        show_view(
+            turn_order.current_player(),
+            &world_state,
+            &empire_query,
+            &mut tile_query,
+            &mut unit_query,
+        );
+    }
+}
+
+/// Hands the controls, selection, camera and view over to the player whose
+/// turn it is
+pub fn switch_player(
+    turn_order: Res<TurnOrder>,
+    mut selector_state: ResMut<controls::SelectorState>,
+    world_state: Res<world_gen::WorldState>,
+    empire_query: Query<&empire::Empire>,
+    mut tile_query: Query<(&tile::TileComponent, &mut Visibility)>,
+    mut unit_query: Query<(&unit::Unit, &mut Visibility), Without<tile::TileComponent>>,
+    camera: Query<&mut Transform, With<Camera3d>>,
+) {
+    let player = turn_order.current_player();
+    show_view(
+        player,
+        &world_state,
+        &empire_query,
+        &mut tile_query,
+        &mut unit_query,
+    );
+
+    selector_state.local_empire = player;
+    selector_state.selected_empire = world_state.empires.get(&player).copied();
+    selector_state.selected_unit = None;
+    selector_state.selected_tile = None;
+
+    let capital = tile_query.iter().map(|(tile, _)| tile).find(|tile| {
+        tile.owner == Some(player) && matches!(tile.building, Some(building::Building::Capital(_)))
+    });
+
+    if let Some(capital) = capital {
+        controls::move_camera_to(camera, utils::to_transform(&capital.tile.location));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{actions, headless};
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn players_only_see_what_they_explored() {
+        let mut app = headless::test_game(config::GameSettings {
+            human_players: 2,
+            ..headless::test_settings(3)
+        });
+        headless::send(&mut app, 0, actions::Action::EndTurn);
+        app.world.run_system_once(switch_player);
+
+        let explored = app
+            .world
+            .query::<&empire::Empire>()
+            .iter(&app.world)
+            .find(|empire| empire.id == 1)
+            .unwrap()
+            .explored
+            .clone();
+        assert!(!explored.is_empty());
+        for (tile, visibility) in app
+            .world
+            .query::<(&tile::TileComponent, &Visibility)>()
+            .iter(&app.world)
+        {
+            let hidden = *visibility == Visibility::Hidden;
+            assert_eq!(hidden, !explored.contains(&tile.tile.location));
+        }
+
+        // Player 0's settler stands far away from anything player 1 explored
+        for (unit, visibility) in app
+            .world
+            .query::<(&unit::Unit, &Visibility)>()
+            .iter(&app.world)
+        {
+            let hidden = *visibility == Visibility::Hidden;
+            assert_eq!(hidden, unit.owner == Some(0));
+        }
+    }
+}
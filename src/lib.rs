@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+
+pub mod actions;
+pub mod animation;
+pub mod ascii_map;
+pub mod bot;
+pub mod building;
+pub mod checksum;
+pub mod colors;
+pub mod config;
+pub mod controls;
+pub mod editor;
+pub mod empire;
+pub mod env;
+pub mod geography;
+pub mod headless;
+pub mod hotseat;
+pub mod map_image;
+pub mod net;
+pub mod replay;
+pub mod resource;
+pub mod scenario;
+pub mod server;
+pub mod state;
+pub mod tick;
+pub mod tile;
+pub mod triggers;
+pub mod ui;
+pub mod undo;
+pub mod unit;
+pub mod utils;
+pub mod world_gen;
+
+pub fn add_resources(
+    mut commands: Commands,
+    materials: ResMut<Assets<StandardMaterial>>,
+    meshes: ResMut<Assets<Mesh>>,
+    asset_server: Res<AssetServer>,
+) {
+    let (tile_resources, materials, meshes) = tile::create_tile_resources(materials, meshes);
+    commands.insert_resource(tile_resources);
+
+    let (unit_resources, materials, _meshes) =
+        unit::create_resources(materials, meshes, &asset_server);
+    commands.insert_resource(unit_resources);
+
+    let (building_resources, _materials) =
+        building::create_building_resources(materials, &asset_server);
+    commands.insert_resource(building_resources);
+}
+
+pub fn add_events(app: &mut App) {
+    app.add_event::<controls::InspectTileEvent>();
+    app.add_event::<controls::SelectUnit>();
+    app.add_event::<controls::DragEvent>();
+    app.add_event::<tick::ActionEvent>();
+    app.add_event::<tick::EndTurnEvent>();
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+    // #[test]
+    // fn basic_ecs () {
+    //     let mut app = App::new();
+
+    //     app.update();
+    // }
+
+    #[test]
+    fn spawns_right_number_of_empires() {
+        let mut app = App::new();
+
+        crate::headless::add_plugins(&mut app);
+        app.insert_resource(crate::config::GameSettings {
+            number_of_empires: 4,
+            ..default()
+        });
+        crate::headless::add_new_game(&mut app);
+
+        app.update();
+
+        assert_eq!(
+            app.world
+                .query::<&crate::empire::Empire>()
+                .iter(&app.world)
+                .len(),
+            4
+        );
+    }
+}
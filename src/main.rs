@@ -3,20 +3,10 @@ use bevy::pbr::ClusterConfig;
 use bevy::prelude::*;
 use bevy_mod_picking::prelude::*;
 
-mod actions;
-mod animation;
-mod building;
-mod colors;
-mod config;
-mod controls;
-mod empire;
-mod resource;
-mod tick;
-mod tile;
-mod ui;
-mod unit;
-mod utils;
-mod world_gen;
+use unciv::{
+    animation, bot, checksum, config, controls, editor, hotseat, net, replay, scenario, state,
+    tick, triggers, ui, undo, world_gen,
+};
 
 fn main() {
     let mut app = App::new();
@@ -27,71 +17,168 @@ fn main() {
 
     // app.insert_resource(DebugPickingMode::Normal);
 
+    app.init_state::<state::AppState>();
+    app.insert_resource(settings_from_args());
+
     app.add_systems(
         Startup,
+        (ui::fps::setup, (setup, unciv::add_resources)).chain(),
+    )
+    .add_systems(
+        OnEnter(state::AppState::MainMenu),
+        (
+            replay::stop,
+            net::stop,
+            bot::stop,
+            state::cleanup_game,
+            ui::menu::init_main_menu,
+        ),
+    )
+    .add_systems(OnExit(state::AppState::MainMenu), ui::menu::despawn_menu)
+    .add_systems(OnEnter(state::AppState::Lobby), ui::menu::init_lobby)
+    .add_systems(OnExit(state::AppState::Lobby), ui::menu::despawn_menu)
+    .add_systems(
+        OnEnter(state::AppState::Loading),
         (
-            ui::fps::setup,
-            (setup, add_resources),
+            state::cleanup_game,
             world_gen::spawn,
+            hotseat::init,
+            replay::start_recording.run_if(not(resource_exists::<replay::Replay>)),
+            checksum::init,
+            undo::init,
+            bot::start,
             controls::init_state,
+            net::assign_local_empire.run_if(resource_exists::<net::NetSession>),
+            hotseat::init_view,
             (
                 ui::panels::init,
                 ui::panels::init_tile_inspector,
                 ui::tick_panel::init,
+                ui::replay_panel::init.run_if(resource_exists::<replay::Replay>),
+                ui::scenario_panel::init.run_if(resource_exists::<scenario::ActiveScenario>),
             ),
+            state::finish_loading,
         )
             .chain(),
     )
+    .add_systems(
+        OnEnter(state::AppState::PassTurn),
+        (hotseat::switch_player, ui::menu::init_pass_turn),
+    )
+    .add_systems(OnExit(state::AppState::PassTurn), ui::menu::despawn_menu)
+    .add_systems(OnEnter(state::AppState::Paused), ui::menu::init_pause_menu)
+    .add_systems(OnExit(state::AppState::Paused), ui::menu::despawn_menu)
+    .add_systems(
+        OnEnter(state::AppState::Editor),
+        (editor::init, ui::editor_panel::init),
+    )
+    .add_systems(
+        OnExit(state::AppState::Editor),
+        (editor::finish, ui::editor_panel::despawn),
+    )
+    .add_systems(OnEnter(state::AppState::GameOver), ui::menu::init_game_over)
+    .add_systems(OnExit(state::AppState::GameOver), ui::menu::despawn_menu)
+    .add_systems(
+        Update,
+        (
+            state::toggle_pause,
+            editor::toggle,
+            ui::button::button_system,
+            ui::menu::menu_button_system,
+            (
+                ui::fps::fps_text_update_system,
+                ui::fps::fps_counter_showhide,
+            ),
+            net::update_lobby.run_if(in_state(state::AppState::Lobby)),
+            net::flush.run_if(resource_exists::<net::NetSession>),
+        ),
+    )
     .add_systems(
         Update,
         ((
             (
                 controls::handle_keyboard,
-                controls::update_selection,
+                controls::update_selection.run_if(not(resource_exists::<replay::Replay>)),
                 controls::handle_drag,
                 controls::handle_mouse_scroll,
             ),
+            (
+                replay::handle_keyboard,
+                ui::replay_panel::replay_button_system,
+                replay::drive,
+                ui::replay_panel::update,
+            )
+                .chain()
+                .run_if(resource_exists::<replay::Replay>),
             (
                 ui::panels::update_tile_inspector,
                 ui::panels::update_empire_panel,
-                ui::button::button_system,
-            ),
-            (
-                ui::fps::fps_text_update_system,
-                ui::fps::fps_counter_showhide,
+                ui::panels::update_unit_panel,
+                ui::scenario_panel::update.run_if(resource_exists::<scenario::ActiveScenario>),
             ),
+            net::receive_turns.run_if(resource_exists::<net::NetSession>),
+            bot::drive.run_if(resource_exists::<bot::Bots>),
             tick::execute_actions,
             tick::tick_world,
+            triggers::evaluate,
+            checksum::record,
             animation::translations,
+            state::check_game_over,
+            scenario::check_turn_limit,
         ))
-            .chain(),
+            .chain()
+            .run_if(in_state(state::AppState::InGame)),
+    )
+    .add_systems(
+        Update,
+        (
+            editor::handle_keyboard,
+            editor::paint,
+            ui::editor_panel::update,
+            controls::handle_drag,
+            controls::handle_mouse_scroll,
+        )
+            .chain()
+            .run_if(in_state(state::AppState::Editor)),
     );
 
-    app.add_event::<controls::InspectTileEvent>();
-    app.add_event::<controls::SelectUnit>();
-    app.add_event::<controls::DragEvent>();
-    app.add_event::<tick::ActionEvent>();
-    app.add_event::<tick::EndTurnEvent>();
+    unciv::add_events(&mut app);
 
     app.run();
 }
 
-fn add_resources(
-    mut commands: Commands,
-    materials: ResMut<Assets<StandardMaterial>>,
-    meshes: ResMut<Assets<Mesh>>,
-    asset_server: Res<AssetServer>,
-) {
-    let (tile_resources, materials, meshes) = tile::create_tile_resources(materials, meshes);
-    commands.insert_resource(tile_resources);
-
-    let (unit_resources, materials, _meshes) =
-        unit::create_resources(materials, meshes, &asset_server);
-    commands.insert_resource(unit_resources);
+/// `--address <host:port>` sets where network games are hosted or joined,
+/// `--bot <command>` and `--bot-address <host:port>` let external programs
+/// play the empires after the human players, `--map-type <name>` picks one of
+/// `config::MAP_TYPES`, `--map <png>` starts on an imported map and
+/// `--scenario <json>` plays a `scenario::Scenario`
+fn settings_from_args() -> config::GameSettings {
+    let mut settings = config::GameSettings::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--address", Some(address)) => settings.address = address,
+            ("--bot", Some(command)) => settings.bots.push(config::BotSpec::Command(command)),
+            ("--bot-address", Some(address)) => {
+                settings.bots.push(config::BotSpec::Address(address))
+            }
+            ("--map", Some(path)) => settings.starting_map = Some(path),
+            ("--scenario", Some(path)) => settings.scenario = Some(path),
+            ("--map-type", Some(name)) => {
+                match config::MAP_TYPES
+                    .into_iter()
+                    .find(|map_type| format!("{:?}", map_type).eq_ignore_ascii_case(&name))
+                {
+                    Some(map_type) => settings.map_type = map_type,
+                    None => println!("Unknown map type: {}", name),
+                }
+            }
+            _ => println!("Unknown argument: {}", arg),
+        }
+    }
 
-    let (building_resources, _materials) =
-        building::create_building_resources(materials, &asset_server);
-    commands.insert_resource(building_resources);
+    settings
 }
 
 fn setup(mut commands: Commands, mut ambient_light: ResMut<AmbientLight>) {
@@ -110,34 +197,3 @@ fn setup(mut commands: Commands, mut ambient_light: ResMut<AmbientLight>) {
     ambient_light.color = Color::WHITE;
     ambient_light.brightness = 500.;
 }
-
-#[cfg(test)]
-mod test {
-    use bevy::prelude::*;
-    // #[test]
-    // fn basic_ecs () {
-    //     let mut app = App::new();
-
-    //     app.update();
-    // }
-
-    #[test]
-    fn spawns_right_number_of_empires() {
-        let mut app = App::new();
-
-        app.add_systems(
-            Update,
-            (crate::add_resources, crate::world_gen::spawn).chain(),
-        );
-
-        app.update();
-
-        assert_eq!(
-            app.world
-                .query::<&crate::empire::Empire>()
-                .iter(&app.world)
-                .len(),
-            4
-        );
-    }
-}
@@ -0,0 +1,211 @@
+use bevy::{prelude::Color, utils::HashMap};
+use image::{ImageBuffer, Luma, Rgb};
+
+use crate::{empire, tile, utils, world_gen};
+
+/// `export` writes `<prefix>.terrain.png`, `<prefix>.height.png` and
+/// `<prefix>.owner.png`
+pub const EXPORT_PREFIX: &str = "map";
+
+/// One color per tile kind in terrain images, painted maps are matched to the
+/// closest color
+const TERRAIN_COLORS: [(tile::TileKind, [u8; 3]); 14] = [
+    (tile::TileKind::Ocean, [20, 50, 120]),
+    (tile::TileKind::Shallows, [60, 110, 190]),
+    (tile::TileKind::Beach, [230, 215, 150]),
+    (tile::TileKind::Desert, [210, 180, 90]),
+    (tile::TileKind::Forest, [30, 110, 40]),
+    (tile::TileKind::Mountain, [120, 110, 100]),
+    (tile::TileKind::SnowyMountain, [230, 230, 240]),
+    (tile::TileKind::Grassland, [100, 180, 60]),
+    (tile::TileKind::Plains, [170, 170, 80]),
+    (tile::TileKind::Tundra, [140, 150, 130]),
+    (tile::TileKind::Snow, [250, 250, 255]),
+    (tile::TileKind::Jungle, [10, 80, 30]),
+    (tile::TileKind::Swamp, [70, 90, 60]),
+    (tile::TileKind::Hills, [140, 110, 70]),
+];
+
+/// Land tiles painted in this color get a river
+const RIVER_COLOR: [u8; 3] = [90, 200, 230];
+
+/// Rivers are written halfway between their tile's color and `RIVER_COLOR`,
+/// so the kind under the river is kept
+fn river_color(color: &[u8; 3]) -> [u8; 3] {
+    [0, 1, 2].map(|i| ((color[i] as u16 + RIVER_COLOR[i] as u16) / 2) as u8)
+}
+
+fn terrain_color(tile: &tile::Tile) -> [u8; 3] {
+    let color = TERRAIN_COLORS
+        .iter()
+        .find(|(kind, _)| *kind == tile.kind)
+        .map_or([0, 0, 0], |(_, color)| *color);
+
+    match tile.river {
+        true => river_color(&color),
+        false => color,
+    }
+}
+
+/// The kind whose color is closest, and if the pixel is a river. Rivers
+/// painted in plain `RIVER_COLOR` run through grassland
+fn closest_terrain(pixel: [u8; 3]) -> (tile::TileKind, bool) {
+    let distance = |color: &[u8; 3]| -> i32 {
+        (0..3)
+            .map(|i| (color[i] as i32 - pixel[i] as i32).pow(2))
+            .sum()
+    };
+
+    let candidates = TERRAIN_COLORS
+        .iter()
+        .flat_map(|(kind, color)| [(*kind, false, *color), (*kind, true, river_color(color))])
+        .chain([(tile::TileKind::Grassland, true, RIVER_COLOR)]);
+    let (kind, river, _) = candidates
+        .min_by_key(|(_, _, color)| distance(color))
+        .unwrap();
+
+    (kind, river)
+}
+
+fn map_size(tiles: &[&tile::TileComponent]) -> (u32, u32) {
+    let width = tiles.iter().map(|tile| tile.tile.location.x + 1).max();
+    let height = tiles.iter().map(|tile| tile.tile.location.y + 1).max();
+
+    (width.unwrap_or(0) as u32, height.unwrap_or(0) as u32)
+}
+
+/// Image rows go down, map rows go up
+fn to_pixel(location: &utils::Coordinates, height: u32) -> (u32, u32) {
+    (location.x as u32, height - 1 - location.y as u32)
+}
+
+/// Writes the terrain, height and owner images of the map
+pub fn export<'a>(
+    tiles: impl Iterator<Item = &'a tile::TileComponent>,
+    prefix: &str,
+) -> image::ImageResult<()> {
+    let tiles: Vec<_> = tiles.collect();
+    let (width, height) = map_size(&tiles);
+
+    let mut terrain = ImageBuffer::<Rgb<u8>, _>::new(width, height);
+    let mut heights = ImageBuffer::<Luma<u16>, _>::new(width, height);
+    let mut owners = ImageBuffer::<Rgb<u8>, _>::new(width, height);
+
+    for tile in tiles {
+        let (x, y) = to_pixel(&tile.tile.location, height);
+
+        terrain.put_pixel(x, y, Rgb(terrain_color(&tile.tile)));
+
+        // Tile heights are flat on water, the kind tells how deep it was
+        let level = match tile::is_land(&tile.tile.kind) {
+            true => world_gen::generator_height(tile.tile.height),
+            false => world_gen::typical_height(&tile.tile.kind),
+        };
+        // Rounded up so the shore, which lies just above the water level, stays land
+        heights.put_pixel(
+            x,
+            y,
+            Luma([((level + 1.) / 2. * u16::MAX as f64).ceil() as u16]),
+        );
+
+        if let Some(owner) = tile.owner {
+            let [r, g, b, _] = Color::hsl(empire::id_to_hue(owner), 0.8, 0.5).as_rgba_u8();
+            owners.put_pixel(x, y, Rgb([r, g, b]));
+        }
+    }
+
+    terrain.save(format!("{}.terrain.png", prefix))?;
+    heights.save(format!("{}.height.png", prefix))?;
+    owners.save(format!("{}.owner.png", prefix))
+}
+
+/// Reads a map painted as terrain colors, or a grayscale heightmap whose
+/// climate and rivers come from the seed
+pub fn import(path: &str, seed: u32) -> image::ImageResult<Vec<tile::TileComponent>> {
+    let image = image::open(path)?;
+    let (width, height) = (image.width(), image.height());
+
+    let rgb = image.to_rgb8();
+    let grayscale = rgb.pixels().all(|Rgb([r, g, b])| r == g && g == b);
+
+    if grayscale {
+        let luma = image.to_luma16();
+        let mut heights = HashMap::new();
+        for y in 0..height {
+            for x in 0..width {
+                let location = utils::Coordinates {
+                    x: x as i32,
+                    y: (height - 1 - y) as i32,
+                };
+                let level = luma.get_pixel(x, y).0[0] as f64 / u16::MAX as f64;
+                heights.insert(location, level * 2. - 1.);
+            }
+        }
+
+        return Ok(world_gen::tiles_from_heights(
+            &heights,
+            width as i32,
+            height as i32,
+            seed,
+        ));
+    }
+
+    let mut tiles = vec![];
+    for (x, y, pixel) in rgb.enumerate_pixels() {
+        let (kind, river) = closest_terrain(pixel.0);
+        let level = world_gen::tile_height(world_gen::typical_height(&kind));
+
+        tiles.push(tile::TileComponent {
+            tile: tile::Tile {
+                location: utils::Coordinates {
+                    x: x as i32,
+                    y: (height - 1 - y) as i32,
+                },
+                kind,
+                height: level,
+                river,
+                body: 0,
+            },
+            owner: None,
+            building: None,
+        });
+    }
+
+    Ok(tiles)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config;
+
+    #[test]
+    fn terrain_images_round_trip() {
+        let tiles = world_gen::spawn_tile_data(30, 20, 3, config::MapType::Continents);
+        let prefix = std::env::temp_dir()
+            .join("unciv-map-image-test")
+            .to_string_lossy()
+            .to_string();
+
+        export(tiles.iter(), &prefix).unwrap();
+        let imported = import(&format!("{}.terrain.png", prefix), 3).unwrap();
+        let heightmap = import(&format!("{}.height.png", prefix), 3).unwrap();
+
+        assert!(tiles.iter().any(|tile| tile.tile.river));
+        assert!(tiles
+            .iter()
+            .any(|tile| tile.tile.kind == tile::TileKind::Ocean));
+        for imported in [imported, heightmap] {
+            assert_eq!(imported.len(), tiles.len());
+            for tile in tiles.iter() {
+                let same = imported
+                    .iter()
+                    .find(|imported| imported.tile.location == tile.tile.location)
+                    .unwrap();
+
+                assert_eq!(same.tile.kind, tile.tile.kind);
+                assert_eq!(same.tile.river, tile.tile.river);
+            }
+        }
+    }
+}
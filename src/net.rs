@@ -0,0 +1,717 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{actions, checksum, config, controls, replay, server, state, tick, world_gen};
+
+/// Messages exchanged between peers, sent as one JSON object per line
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum NetMessage {
+    Join,
+    Welcome {
+        empire: i32,
+        header: replay::ReplayHeader,
+    },
+    TurnActions {
+        turn: u32,
+        empire: i32,
+        actions: Vec<actions::Action>,
+        /// The sender's state hash at the start of the turn
+        state_hash: Option<u64>,
+    },
+    /// Sent to a dedicated server, which executes it for the sender's empire
+    Action {
+        action: actions::Action,
+    },
+    Rejected {
+        reason: String,
+    },
+    Diff(server::StateDiff),
+}
+
+pub struct Connection {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    /// Bytes the socket did not take yet, written by `flush`
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Connection {
+            stream,
+            buffer: vec![],
+            outgoing: vec![],
+        })
+    }
+
+    pub fn send(&mut self, message: &NetMessage) -> std::io::Result<()> {
+        self.outgoing.extend(serde_json::to_vec(message)?);
+        self.outgoing.push(b'\n');
+
+        self.flush()
+    }
+
+    /// Writes as much of the queued bytes as the socket takes without
+    /// blocking, the rest is tried again on the next call
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        let mut written = 0;
+        let result = loop {
+            if written == self.outgoing.len() {
+                break Ok(());
+            }
+            match self.stream.write(&self.outgoing[written..]) {
+                Ok(0) => break Err(ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(error) => break Err(error),
+            }
+        };
+        self.outgoing.drain(..written);
+
+        result
+    }
+
+    /// Returns every complete message received since the last poll
+    pub fn poll(&mut self) -> std::io::Result<Vec<NetMessage>> {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(ErrorKind::ConnectionAborted.into()),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        let mut messages = vec![];
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            messages.push(serde_json::from_slice(&line)?);
+        }
+
+        Ok(messages)
+    }
+}
+
+/// Collects the orders of every player for a turn, the turn is only applied
+/// once all of them arrived so every peer executes the same actions in the
+/// same order
+pub struct Lockstep {
+    pub empire: i32,
+    pub players: Vec<i32>,
+    pub turn: u32,
+    /// Orders of the local player and the bots it runs, with their empire
+    pending: Vec<(i32, actions::Action)>,
+    received: HashMap<(u32, i32), Vec<actions::Action>>,
+}
+
+impl Lockstep {
+    pub fn new(empire: i32, players: Vec<i32>) -> Self {
+        Lockstep {
+            empire,
+            players,
+            turn: 0,
+            pending: vec![],
+            received: HashMap::new(),
+        }
+    }
+
+    /// Queues an order issued on this machine, returns the messages to
+    /// broadcast once the local player ends their turn. Bots' orders are sent
+    /// first, their turn ends with the local player's
+    pub fn queue_local(
+        &mut self,
+        empire: i32,
+        action: actions::Action,
+        state_hash: Option<u64>,
+    ) -> Vec<NetMessage> {
+        // Orders are only sent at the end of the turn, so undoing drops the last one
+        if let actions::Action::Undo = action {
+            if let Some(index) = self
+                .pending
+                .iter()
+                .rposition(|(issuer, _)| *issuer == empire)
+            {
+                self.pending.remove(index);
+            }
+            return vec![];
+        }
+        if !matches!(action, actions::Action::EndTurn) {
+            self.pending.push((empire, action));
+            return vec![];
+        }
+        if empire != self.empire {
+            return vec![];
+        }
+        if self.received.contains_key(&(self.turn, self.empire)) {
+            println!("Turn {} already ended", self.turn);
+            return vec![];
+        }
+
+        let mut by_empire: HashMap<i32, Vec<actions::Action>> = HashMap::new();
+        for (issuer, action) in std::mem::take(&mut self.pending) {
+            by_empire.entry(issuer).or_default().push(action);
+        }
+        let mut bots: Vec<i32> = by_empire
+            .keys()
+            .copied()
+            .filter(|issuer| *issuer != self.empire)
+            .collect();
+        bots.sort();
+
+        let mut messages = vec![];
+        for issuer in bots.into_iter().chain([self.empire]) {
+            let actions = by_empire.remove(&issuer).unwrap_or_default();
+            self.received.insert((self.turn, issuer), actions.clone());
+            messages.push(NetMessage::TurnActions {
+                turn: self.turn,
+                empire: issuer,
+                actions,
+                state_hash: match issuer == self.empire {
+                    true => state_hash,
+                    false => None,
+                },
+            });
+        }
+
+        messages
+    }
+
+    pub fn receive(&mut self, turn: u32, empire: i32, actions: Vec<actions::Action>) {
+        self.received.insert((turn, empire), actions);
+    }
+
+    /// The actions of the current turn ordered by empire, if every player sent
+    /// theirs. Bots' orders arrive before the turn of the player running them,
+    /// so they are included as well. End of turns are not included
+    pub fn take_ready_turn(&mut self) -> Option<Vec<(i32, actions::Action)>> {
+        let turn = self.turn;
+        if !self
+            .players
+            .iter()
+            .all(|empire| self.received.contains_key(&(turn, *empire)))
+        {
+            return None;
+        }
+
+        let mut empires: Vec<i32> = self
+            .received
+            .keys()
+            .filter(|(received_turn, _)| *received_turn == turn)
+            .map(|(_, empire)| *empire)
+            .collect();
+        empires.sort();
+
+        let mut turn_actions = vec![];
+        for empire in empires {
+            for action in self.received.remove(&(turn, empire)).unwrap() {
+                turn_actions.push((empire, action));
+            }
+        }
+
+        self.turn += 1;
+
+        Some(turn_actions)
+    }
+}
+
+pub enum Role {
+    Host {
+        listener: TcpListener,
+        clients: Vec<(i32, Connection)>,
+    },
+    Client {
+        host: Connection,
+    },
+}
+
+#[derive(Resource)]
+pub struct NetSession {
+    pub role: Role,
+    /// Only set once the game started, until then the peers are in the lobby
+    pub lockstep: Option<Lockstep>,
+    /// Messages that arrived in the lobby along with the welcome, handed out
+    /// by the next `poll`
+    backlog: Vec<NetMessage>,
+}
+
+impl NetSession {
+    pub fn host(address: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(NetSession {
+            role: Role::Host {
+                listener,
+                clients: vec![],
+            },
+            lockstep: None,
+            backlog: vec![],
+        })
+    }
+
+    pub fn join(address: &str) -> std::io::Result<Self> {
+        let mut host = Connection::new(TcpStream::connect(address)?)?;
+        host.send(&NetMessage::Join)?;
+
+        Ok(NetSession {
+            role: Role::Client { host },
+            lockstep: None,
+            backlog: vec![],
+        })
+    }
+
+    /// Sends a message to every other peer, the host relays for the clients
+    pub fn broadcast(&mut self, message: &NetMessage) {
+        match &mut self.role {
+            Role::Host { clients, .. } => {
+                for (empire, client) in clients.iter_mut() {
+                    if let Err(error) = client.send(message) {
+                        println!("Could not send to empire {}: {}", empire, error);
+                    }
+                }
+            }
+            Role::Client { host } => {
+                if let Err(error) = host.send(message) {
+                    println!("Could not send to host: {}", error);
+                }
+            }
+        }
+    }
+
+    /// Accepts new clients while in the lobby, returns the number of connected clients
+    pub fn accept_clients(&mut self) -> usize {
+        let Role::Host { listener, clients } = &mut self.role else {
+            return 0;
+        };
+
+        loop {
+            match listener.accept() {
+                Ok((stream, address)) => match Connection::new(stream) {
+                    Ok(connection) => {
+                        println!("Player joined from {}", address);
+                        let empire = clients.len() as i32 + 1;
+                        clients.push((empire, connection));
+                    }
+                    Err(error) => println!("Could not accept {}: {}", address, error),
+                },
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    println!("Could not accept player: {}", error);
+                    break;
+                }
+            }
+        }
+
+        clients.len()
+    }
+
+    /// Reads all pending messages, relaying turn actions between clients when hosting
+    pub fn poll(&mut self) -> Vec<NetMessage> {
+        let mut messages = std::mem::take(&mut self.backlog);
+
+        match &mut self.role {
+            Role::Host { clients, .. } => {
+                let mut relayed = vec![];
+                for (empire, client) in clients.iter_mut() {
+                    match client.poll() {
+                        Ok(received) => {
+                            for message in received {
+                                if let NetMessage::TurnActions { .. } = message {
+                                    relayed.push((*empire, message.clone()));
+                                }
+                                messages.push(message);
+                            }
+                        }
+                        Err(error) => println!("Lost connection to empire {}: {}", empire, error),
+                    }
+                }
+
+                for (sender, message) in relayed {
+                    for (empire, client) in clients.iter_mut() {
+                        if *empire != sender {
+                            if let Err(error) = client.send(&message) {
+                                println!("Could not relay to empire {}: {}", empire, error);
+                            }
+                        }
+                    }
+                }
+            }
+            Role::Client { host } => match host.poll() {
+                Ok(received) => messages.extend(received),
+                Err(error) => println!("Lost connection to host: {}", error),
+            },
+        }
+
+        messages
+    }
+
+    /// Sends what the sockets did not take earlier
+    pub fn flush(&mut self) {
+        match &mut self.role {
+            Role::Host { clients, .. } => {
+                for (empire, client) in clients.iter_mut() {
+                    if let Err(error) = client.flush() {
+                        println!("Could not send to empire {}: {}", empire, error);
+                    }
+                }
+            }
+            Role::Client { host } => {
+                if let Err(error) = host.flush() {
+                    println!("Could not send to host: {}", error);
+                }
+            }
+        }
+    }
+}
+
+pub fn host(
+    commands: &mut Commands,
+    settings: &config::GameSettings,
+    next_state: &mut NextState<state::AppState>,
+) {
+    match NetSession::host(&settings.address) {
+        Ok(session) => {
+            println!("Hosting on {}", settings.address);
+            commands.insert_resource(session);
+            next_state.set(state::AppState::Lobby);
+        }
+        Err(error) => println!("Could not host on {}: {}", settings.address, error),
+    }
+}
+
+pub fn join(
+    commands: &mut Commands,
+    settings: &config::GameSettings,
+    next_state: &mut NextState<state::AppState>,
+) {
+    match NetSession::join(&settings.address) {
+        Ok(session) => {
+            println!("Joined {}", settings.address);
+            commands.insert_resource(session);
+            next_state.set(state::AppState::Lobby);
+        }
+        Err(error) => println!("Could not join {}: {}", settings.address, error),
+    }
+}
+
+/// Waits for every player, the host then tells each client its empire and
+/// the settings to generate the shared world from
+pub fn update_lobby(
+    mut session: ResMut<NetSession>,
+    mut settings: ResMut<config::GameSettings>,
+    mut next_state: ResMut<NextState<state::AppState>>,
+) {
+    let players = settings.human_players.clamp(1, settings.number_of_empires);
+
+    if let Role::Host { .. } = session.role {
+        if session.accept_clients() + 1 < players as usize {
+            return;
+        }
+
+        let header = replay::ReplayHeader {
+            seed: settings.seed,
+            number_of_empires: settings.number_of_empires,
+            human_players: players,
+            world_size: settings.world_size,
+            map_type: settings.map_type,
+            starting_map: settings.starting_map.clone(),
+            scenario: settings.scenario.clone(),
+        };
+        if let Role::Host { clients, .. } = &mut session.role {
+            for (empire, client) in clients.iter_mut() {
+                let welcome = NetMessage::Welcome {
+                    empire: *empire,
+                    header: header.clone(),
+                };
+                if let Err(error) = client.send(&welcome) {
+                    println!("Could not welcome empire {}: {}", empire, error);
+                }
+            }
+        }
+
+        session.lockstep = Some(Lockstep::new(0, (0..players).collect()));
+        next_state.set(state::AppState::Loading);
+        return;
+    }
+
+    for message in session.poll() {
+        // Peers may already play once welcomed, their turns wait for `receive_turns`
+        if session.lockstep.is_some() {
+            session.backlog.push(message);
+            continue;
+        }
+
+        if let NetMessage::Welcome { empire, header } = message {
+            settings.seed = header.seed;
+            settings.number_of_empires = header.number_of_empires;
+            settings.human_players = header.human_players;
+            settings.world_size = header.world_size;
+            settings.map_type = header.map_type;
+            settings.starting_map = header.starting_map;
+            settings.scenario = header.scenario;
+
+            session.lockstep = Some(Lockstep::new(empire, (0..header.human_players).collect()));
+            next_state.set(state::AppState::Loading);
+        }
+    }
+}
+
+pub fn assign_local_empire(
+    session: Res<NetSession>,
+    mut selector_state: ResMut<controls::SelectorState>,
+    world_state: Res<world_gen::WorldState>,
+) {
+    if let Some(lockstep) = &session.lockstep {
+        selector_state.local_empire = lockstep.empire;
+        selector_state.selected_empire = world_state.empires.get(&lockstep.empire).copied();
+    }
+}
+
+/// Called for every action issued on this machine, by the player or a bot,
+/// it is only executed once the whole turn is known
+pub fn queue_local(
+    session: &mut NetSession,
+    empire: i32,
+    action: actions::Action,
+    state_hash: Option<u64>,
+) {
+    let messages = match &mut session.lockstep {
+        Some(lockstep) => lockstep.queue_local(empire, action, state_hash),
+        None => vec![],
+    };
+
+    for message in messages {
+        session.broadcast(&message);
+    }
+}
+
+pub fn receive_turns(
+    mut session: ResMut<NetSession>,
+    mut action_writer: EventWriter<tick::ActionEvent>,
+    mut state_hashes: ResMut<checksum::StateHashes>,
+) {
+    for message in session.poll() {
+        if let (
+            NetMessage::TurnActions {
+                turn,
+                empire,
+                actions,
+                state_hash,
+            },
+            Some(lockstep),
+        ) = (message, &mut session.lockstep)
+        {
+            if let Some(state_hash) = state_hash {
+                state_hashes.expect(turn, state_hash);
+            }
+            lockstep.receive(turn, empire, actions);
+        }
+    }
+
+    let Some(lockstep) = &mut session.lockstep else {
+        return;
+    };
+
+    if let Some(turn_actions) = lockstep.take_ready_turn() {
+        for (empire, action) in turn_actions {
+            action_writer.send(tick::ActionEvent {
+                action,
+                empire,
+                source: tick::ActionSource::Network,
+            });
+        }
+        // Ending the turn for every player in order lets `hotseat::TurnOrder`
+        // tick the world exactly once, also when the game is replayed
+        let mut players = lockstep.players.clone();
+        players.sort();
+        for empire in players {
+            action_writer.send(tick::ActionEvent {
+                action: actions::Action::EndTurn,
+                empire,
+                source: tick::ActionSource::Network,
+            });
+        }
+    }
+}
+
+pub fn flush(mut session: ResMut<NetSession>) {
+    session.flush();
+}
+
+pub fn stop(mut commands: Commands) {
+    commands.remove_resource::<NetSession>();
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// Generous for loaded machines, waiting ends as soon as the condition holds
+    const DEADLINE: std::time::Duration = std::time::Duration::from_secs(10);
+
+    /// Retries `done` until it holds, fails the test after `DEADLINE`
+    pub(crate) fn wait_until(what: &str, mut done: impl FnMut() -> bool) {
+        let start = std::time::Instant::now();
+        while !done() {
+            assert!(start.elapsed() < DEADLINE, "Timed out waiting for {}", what);
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    pub(crate) fn wait_for_clients(host: &mut NetSession, count: usize) {
+        wait_until("clients", || host.accept_clients() == count);
+    }
+
+    /// Polls until at least `count` messages arrived
+    pub(crate) fn poll_until(session: &mut NetSession, count: usize) -> Vec<NetMessage> {
+        let mut messages = vec![];
+        wait_until("messages", || {
+            messages.extend(session.poll());
+            messages.len() >= count
+        });
+        messages
+    }
+
+    #[test]
+    fn peers_apply_the_same_turn_over_loopback() {
+        let mut host = NetSession::host("127.0.0.1:0").unwrap();
+        let address = match &host.role {
+            Role::Host { listener, .. } => listener.local_addr().unwrap().to_string(),
+            Role::Client { .. } => unreachable!(),
+        };
+        let mut client = NetSession::join(&address).unwrap();
+        wait_for_clients(&mut host, 1);
+
+        host.lockstep = Some(Lockstep::new(0, vec![0, 1]));
+        client.lockstep = Some(Lockstep::new(1, vec![0, 1]));
+
+        queue_local(&mut client, 1, actions::Action::Noop, None);
+        queue_local(&mut client, 1, actions::Action::EndTurn, Some(1));
+        // A bot run by the host plays empire 2
+        queue_local(&mut host, 2, actions::Action::Noop, None);
+        queue_local(&mut host, 0, actions::Action::EndTurn, Some(1));
+
+        // The client sends its turn, the host the bot's and its own
+        let received = [poll_until(&mut host, 1), poll_until(&mut client, 2)];
+        for (session, messages) in [&mut host, &mut client].into_iter().zip(received) {
+            for message in messages {
+                if let NetMessage::TurnActions {
+                    turn,
+                    empire,
+                    actions,
+                    ..
+                } = message
+                {
+                    session
+                        .lockstep
+                        .as_mut()
+                        .unwrap()
+                        .receive(turn, empire, actions);
+                }
+            }
+        }
+
+        let host_turn = host.lockstep.as_mut().unwrap().take_ready_turn().unwrap();
+        let client_turn = client.lockstep.as_mut().unwrap().take_ready_turn().unwrap();
+
+        let summary = |turn: &Vec<(i32, actions::Action)>| {
+            turn.iter()
+                .map(|(empire, action)| format!("{}:{:?}", empire, action))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summary(&host_turn),
+            vec!["1:Noop".to_string(), "2:Noop".to_string()]
+        );
+        assert_eq!(summary(&host_turn), summary(&client_turn));
+        assert_eq!(host.lockstep.as_ref().unwrap().turn, 1);
+    }
+
+    #[test]
+    fn turns_sent_with_the_welcome_are_kept() {
+        use bevy::ecs::system::RunSystemOnce;
+
+        let mut host = NetSession::host("127.0.0.1:0").unwrap();
+        let address = match &host.role {
+            Role::Host { listener, .. } => listener.local_addr().unwrap().to_string(),
+            Role::Client { .. } => unreachable!(),
+        };
+        let client = NetSession::join(&address).unwrap();
+        wait_for_clients(&mut host, 1);
+
+        let settings = config::GameSettings::default();
+        host.broadcast(&NetMessage::Welcome {
+            empire: 1,
+            header: replay::ReplayHeader {
+                seed: 3,
+                number_of_empires: 2,
+                human_players: 2,
+                world_size: settings.world_size,
+                map_type: settings.map_type,
+                starting_map: None,
+                scenario: None,
+            },
+        });
+        host.broadcast(&NetMessage::TurnActions {
+            turn: 0,
+            empire: 0,
+            actions: vec![actions::Action::Noop],
+            state_hash: None,
+        });
+
+        let mut world = World::new();
+        world.insert_resource(settings);
+        world.insert_resource(client);
+        world.init_resource::<NextState<state::AppState>>();
+        wait_until("the welcome", || {
+            world.run_system_once(update_lobby);
+            world.resource::<NetSession>().lockstep.is_some()
+        });
+
+        let mut client = world.remove_resource::<NetSession>().unwrap();
+        assert_eq!(client.lockstep.as_ref().unwrap().empire, 1);
+        let messages = poll_until(&mut client, 1);
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(
+            messages[0],
+            NetMessage::TurnActions {
+                turn: 0,
+                empire: 0,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn large_messages_are_sent_without_blocking() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sender =
+            Connection::new(TcpStream::connect(listener.local_addr().unwrap()).unwrap()).unwrap();
+        let mut receiver = Connection::new(listener.accept().unwrap().0).unwrap();
+
+        // More than the socket buffers hold while nobody reads
+        let message = NetMessage::Rejected {
+            reason: "x".repeat(1 << 20),
+        };
+        for _ in 0..8 {
+            sender.send(&message).unwrap();
+        }
+        assert!(!sender.outgoing.is_empty());
+
+        let mut received = 0;
+        wait_until("every message", || {
+            sender.flush().unwrap();
+            received += receiver.poll().unwrap().len();
+            received >= 8
+        });
+        assert_eq!(received, 8);
+        assert!(sender.outgoing.is_empty());
+    }
+}
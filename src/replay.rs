@@ -0,0 +1,320 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{actions, config, state, tick, world_gen};
+
+/// Every game is recorded to its own file in here, so older games can still
+/// be attached to bug reports
+pub const REPLAY_DIR: &str = "replays";
+
+/// First line of a replay file, everything needed to regenerate the world
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplayHeader {
+    pub seed: u32,
+    pub number_of_empires: i32,
+    pub human_players: i32,
+    pub world_size: (i32, i32),
+    /// Missing in replays from before map types
+    #[serde(default)]
+    pub map_type: config::MapType,
+    #[serde(default)]
+    pub starting_map: Option<String>,
+    #[serde(default)]
+    pub scenario: Option<String>,
+}
+
+/// Every following line of a replay file is one executed action
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LogEntry {
+    pub turn: u32,
+    pub empire: i32,
+    pub action: actions::Action,
+}
+
+/// Written at the start of every turn between the actions, replays report
+/// where they stop matching the recorded game
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StateHashEntry {
+    pub turn: u32,
+    pub state_hash: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReplayLine {
+    Action(LogEntry),
+    StateHash(StateHashEntry),
+}
+
+/// Records the actions of the game being played
+#[derive(Resource)]
+pub struct ActionLog {
+    writer: LineWriter<File>,
+}
+
+impl ActionLog {
+    pub fn create(path: &str, header: &ReplayHeader) -> std::io::Result<Self> {
+        let mut writer = LineWriter::new(File::create(path)?);
+        writeln!(writer, "{}", serde_json::to_string(header)?)?;
+
+        Ok(ActionLog { writer })
+    }
+
+    pub fn record(&mut self, entry: &LogEntry) {
+        self.write_line(serde_json::to_string(entry).unwrap());
+    }
+
+    pub fn record_state_hash(&mut self, turn: u32, state_hash: u64) {
+        self.write_line(serde_json::to_string(&StateHashEntry { turn, state_hash }).unwrap());
+    }
+
+    fn write_line(&mut self, line: String) {
+        if let Err(error) = writeln!(self.writer, "{}", line) {
+            println!("Could not write to action log: {}", error);
+        }
+    }
+}
+
+/// Present while a recorded game is being played back instead of a live game
+#[derive(Resource)]
+pub struct Replay {
+    pub entries: Vec<LogEntry>,
+    pub state_hashes: Vec<(u32, u64)>,
+    pub cursor: usize,
+    pub playing: bool,
+    pub step_requested: bool,
+    pub timer: Timer,
+}
+
+impl Replay {
+    pub fn last_turn(&self) -> u32 {
+        self.entries.last().map(|entry| entry.turn).unwrap_or(0)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.cursor >= self.entries.len()
+    }
+}
+
+pub struct ReplayFile {
+    pub header: ReplayHeader,
+    pub entries: Vec<LogEntry>,
+    pub state_hashes: Vec<(u32, u64)>,
+}
+
+pub fn read_replay(path: &str) -> std::io::Result<ReplayFile> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+
+    let header_line = lines.next().unwrap_or(Ok(String::new()))?;
+    let header: ReplayHeader = serde_json::from_str(&header_line)?;
+
+    let mut entries = vec![];
+    let mut state_hashes = vec![];
+    for line in lines {
+        match serde_json::from_str(&line?)? {
+            ReplayLine::Action(entry) => entries.push(entry),
+            ReplayLine::StateHash(entry) => state_hashes.push((entry.turn, entry.state_hash)),
+        }
+    }
+
+    Ok(ReplayFile {
+        header,
+        entries,
+        state_hashes,
+    })
+}
+
+/// Named after the time the game started and its seed
+fn replay_path(seed: u32) -> String {
+    let started = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    format!("{}/{}-seed{}.replay", REPLAY_DIR, started, seed)
+}
+
+/// The recorded games, newest first
+pub fn list_replays() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(REPLAY_DIR) else {
+        return vec![];
+    };
+
+    let mut paths: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension() == Some(std::ffi::OsStr::new("replay")))
+        .filter_map(|path| path.to_str().map(String::from))
+        .collect();
+    paths.sort();
+    paths.reverse();
+
+    paths
+}
+
+/// Loads the replay file and starts regenerating its world
+pub fn load(
+    path: &str,
+    commands: &mut Commands,
+    settings: &mut config::GameSettings,
+    next_state: &mut NextState<state::AppState>,
+) {
+    match read_replay(path) {
+        Ok(ReplayFile {
+            header,
+            entries,
+            state_hashes,
+        }) => {
+            settings.seed = header.seed;
+            settings.number_of_empires = header.number_of_empires;
+            settings.human_players = header.human_players;
+            settings.world_size = header.world_size;
+            settings.map_type = header.map_type;
+            settings.starting_map = header.starting_map;
+            settings.scenario = header.scenario;
+
+            commands.insert_resource(Replay {
+                entries,
+                state_hashes,
+                cursor: 0,
+                playing: false,
+                step_requested: false,
+                timer: Timer::from_seconds(1.0, TimerMode::Repeating),
+            });
+            next_state.set(state::AppState::Loading);
+        }
+        Err(error) => {
+            println!("Could not load replay {}: {}", path, error);
+        }
+    }
+}
+
+pub fn start_recording(mut commands: Commands, settings: Res<config::GameSettings>) {
+    let header = ReplayHeader {
+        seed: settings.seed,
+        number_of_empires: settings.number_of_empires,
+        human_players: settings.human_players,
+        world_size: settings.world_size,
+        map_type: settings.map_type,
+        starting_map: settings.starting_map.clone(),
+        scenario: settings.scenario.clone(),
+    };
+
+    let path = replay_path(settings.seed);
+    let action_log =
+        std::fs::create_dir_all(REPLAY_DIR).and_then(|_| ActionLog::create(&path, &header));
+    match action_log {
+        Ok(action_log) => commands.insert_resource(action_log),
+        Err(error) => println!("Could not create action log {}: {}", path, error),
+    }
+}
+
+pub fn stop(mut commands: Commands) {
+    commands.remove_resource::<ActionLog>();
+    commands.remove_resource::<Replay>();
+}
+
+pub fn handle_keyboard(keyboard_input: Res<ButtonInput<KeyCode>>, mut replay: ResMut<Replay>) {
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        replay.playing = !replay.playing;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        replay.step_requested = true;
+    }
+}
+
+/// Sends all recorded actions of the next turn, either on every timer tick
+/// while playing or once per requested step
+pub fn drive(
+    time: Res<Time>,
+    mut replay: ResMut<Replay>,
+    mut action_writer: EventWriter<tick::ActionEvent>,
+) {
+    replay.timer.tick(time.delta());
+
+    let play_tick = replay.playing && replay.timer.just_finished();
+    if !play_tick && !replay.step_requested {
+        return;
+    }
+    replay.step_requested = false;
+
+    let Some(turn) = replay.entries.get(replay.cursor).map(|entry| entry.turn) else {
+        replay.playing = false;
+        return;
+    };
+
+    while let Some(entry) = replay.entries.get(replay.cursor) {
+        if entry.turn != turn {
+            break;
+        }
+
+        action_writer.send(tick::ActionEvent {
+            action: entry.action.clone(),
+            empire: entry.empire,
+            source: tick::ActionSource::Scripted,
+        });
+        replay.cursor += 1;
+    }
+}
+
+pub fn record(
+    action_log: &mut Option<ResMut<ActionLog>>,
+    world_state: &world_gen::WorldState,
+    empire: i32,
+    action: &actions::Action,
+) {
+    if let Some(action_log) = action_log {
+        action_log.record(&LogEntry {
+            turn: world_state.turn,
+            empire,
+            action: action.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{headless, unit};
+
+    #[test]
+    fn rejected_actions_are_not_recorded() {
+        let path = std::env::temp_dir().join("unciv_rejected_actions.replay");
+        let path = path.to_str().unwrap();
+
+        let settings = headless::test_settings(3);
+        let header = ReplayHeader {
+            seed: settings.seed,
+            number_of_empires: settings.number_of_empires,
+            human_players: settings.human_players,
+            world_size: settings.world_size,
+            map_type: settings.map_type,
+            starting_map: None,
+            scenario: None,
+        };
+        let mut app = headless::test_app(settings);
+        app.insert_resource(ActionLog::create(path, &header).unwrap());
+        app.update();
+
+        let foreign_unit = app
+            .world
+            .query::<&unit::Unit>()
+            .iter(&app.world)
+            .find(|unit| unit.owner == Some(1))
+            .unwrap()
+            .id;
+        for action in [
+            actions::Action::KillUnit(foreign_unit),
+            actions::Action::EndTurn,
+        ] {
+            headless::send(&mut app, 0, action);
+        }
+
+        let entries = read_replay(path).unwrap().entries;
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(entries[0].action, actions::Action::EndTurn));
+    }
+}
@@ -1,7 +1,9 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Hash, PartialEq, Eq, Clone, Debug)]
+#[derive(Component, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Debug, Serialize, Deserialize)]
 pub enum Resource {
     Wood,
     Stone,
+    Food,
 }
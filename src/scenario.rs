@@ -0,0 +1,457 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{building, config, empire, resource, state, tile, triggers, unit, utils, world_gen};
+
+pub const SCENARIO_PATH: &str = "scenario.json";
+
+/// A hand made starting situation, written by the map editor or by hand.
+/// Everything but the empires can be left out, the map is then generated
+/// from the seed like in a random game
+#[derive(Serialize, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub seed: Option<u32>,
+    /// Only used when the map is generated
+    #[serde(default)]
+    pub world_size: Option<(i32, i32)>,
+    #[serde(default)]
+    pub map_type: Option<config::MapType>,
+    pub empires: Vec<EmpireSetup>,
+    /// The whole map, owners and buildings included
+    #[serde(default)]
+    pub tiles: Vec<tile::TileComponent>,
+    /// Placed on top of the map
+    #[serde(default)]
+    pub cities: Vec<CitySetup>,
+    #[serde(default)]
+    pub units: Vec<UnitSetup>,
+    /// The game is over once this turn is reached
+    #[serde(default)]
+    pub turn_limit: Option<u32>,
+    #[serde(default)]
+    pub triggers: Vec<triggers::Trigger>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EmpireSetup {
+    pub name: String,
+    #[serde(default)]
+    pub inventory: HashMap<resource::Resource, i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CitySetup {
+    pub location: utils::Coordinates,
+    pub owner: i32,
+    #[serde(default)]
+    pub capital: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UnitSetup {
+    pub kind: unit::UnitKind,
+    pub location: utils::Coordinates,
+    pub owner: i32,
+    #[serde(default)]
+    pub orders: Vec<unit::orders::Order>,
+}
+
+/// The rules of the scenario that is being played
+#[derive(Resource)]
+pub struct ActiveScenario {
+    pub turn_limit: Option<u32>,
+    /// Triggers that can still fire
+    pub triggers: Vec<triggers::Trigger>,
+    /// The last turn the triggers were checked on, `None` before the first
+    pub evaluated_turn: Option<u32>,
+    /// Every message shown so far
+    pub messages: Vec<String>,
+    pub winner: Option<i32>,
+}
+
+/// Captures a running game, sorted so the same world always gives the same
+/// file
+pub fn from_world<'a>(
+    tiles: impl Iterator<Item = &'a tile::TileComponent>,
+    units: impl Iterator<Item = &'a unit::Unit>,
+    empires: impl Iterator<Item = &'a empire::Empire>,
+    seed: u32,
+) -> Scenario {
+    let mut tiles: Vec<tile::TileComponent> = tiles.cloned().collect();
+    tiles.sort_by_key(|tile| (tile.tile.location.x, tile.tile.location.y));
+    let mut units: Vec<&unit::Unit> = units.collect();
+    units.sort_by_key(|unit| unit.id.0);
+    let mut empires: Vec<&empire::Empire> = empires.collect();
+    empires.sort_by_key(|empire| empire.id);
+    let world_size = tiles.iter().fold((0, 0), |(width, height), tile| {
+        (
+            width.max(tile.tile.location.x + 1),
+            height.max(tile.tile.location.y + 1),
+        )
+    });
+
+    Scenario {
+        seed: Some(seed),
+        world_size: Some(world_size),
+        map_type: None,
+        empires: empires
+            .iter()
+            .map(|empire| EmpireSetup {
+                name: empire.name.clone(),
+                inventory: empire.inventory.items.clone(),
+            })
+            .collect(),
+        tiles,
+        cities: vec![],
+        units: units
+            .iter()
+            .filter_map(|unit| {
+                Some(UnitSetup {
+                    kind: unit.kind.clone(),
+                    location: unit.location,
+                    owner: unit.owner?,
+                    orders: unit.orders.clone(),
+                })
+            })
+            .collect(),
+        turn_limit: None,
+        triggers: vec![],
+    }
+}
+
+pub fn save(path: &str, scenario: &Scenario) -> std::io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, scenario)?;
+    Ok(())
+}
+
+pub fn load(path: &str) -> Result<Scenario, String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    let scenario: Scenario =
+        serde_json::from_reader(BufReader::new(file)).map_err(|error| error.to_string())?;
+    validate(&scenario)?;
+    Ok(scenario)
+}
+
+/// Starts a game from the scenario at `SCENARIO_PATH`, like the one the map
+/// editor saves
+pub fn start(settings: &mut config::GameSettings, next_state: &mut NextState<state::AppState>) {
+    match load(SCENARIO_PATH) {
+        Ok(_) => {
+            settings.scenario = Some(SCENARIO_PATH.to_string());
+            next_state.set(state::AppState::Loading);
+        }
+        Err(error) => println!("Could not load scenario {}: {}", SCENARIO_PATH, error),
+    }
+}
+
+/// Makes the settings match the scenario, before anything is spawned
+pub fn apply_settings(scenario: &Scenario, settings: &mut config::GameSettings) {
+    settings.number_of_empires = scenario.empires.len() as i32;
+    settings.human_players = settings.human_players.min(settings.number_of_empires);
+    if let Some(seed) = scenario.seed {
+        settings.seed = seed;
+    }
+    if let Some(world_size) = scenario.world_size {
+        settings.world_size = world_size;
+    }
+    if let Some(map_type) = scenario.map_type {
+        settings.map_type = map_type;
+    }
+}
+
+pub fn place_cities(scenario: &Scenario, tile_data: &mut [tile::TileComponent]) {
+    for city in scenario.cities.iter() {
+        let Some(tile) = tile_data
+            .iter_mut()
+            .find(|tile| tile.tile.location == city.location)
+        else {
+            continue;
+        };
+
+        tile.owner = Some(city.owner);
+        tile.building = Some(match city.capital {
+            true => building::Building::Capital(default()),
+            false => building::Building::City(default()),
+        });
+    }
+}
+
+/// Spawns a unit of the scenario, a trigger or the editor, units can only be
+/// placed on land tiles with room for them
+pub fn spawn_unit(
+    setup: UnitSetup,
+    commands: &mut Commands,
+    world_state: &mut world_gen::WorldState,
+    unit_resources: &Res<unit::UnitResources>,
+) -> Result<unit::UnitId, String> {
+    let tile = world_state
+        .tile_data
+        .get(&setup.location)
+        .ok_or("Outside of the map".to_string())?;
+    if !tile::is_land(&tile.kind) {
+        return Err("Units can't be placed on water".to_string());
+    }
+    world_state
+        .occupancy
+        .can_enter(&setup.location, &setup.kind)?;
+
+    let unit = unit::Unit {
+        id: world_state.next_unit_id(),
+        kind: setup.kind,
+        location: setup.location,
+        owner: Some(setup.owner),
+        orders: setup.orders,
+        ..default()
+    };
+    let unit_id = unit.id;
+    world_state.occupancy.add(&unit);
+    let unit_bundle = unit::make_bundle(unit, unit_resources, &world_state.tile_data);
+    let unit_entity = commands.spawn(unit_bundle).id();
+    world_state.units.insert(unit_id, unit_entity);
+
+    Ok(unit_id)
+}
+
+fn validate(scenario: &Scenario) -> Result<(), String> {
+    if scenario.empires.is_empty() {
+        return Err("Scenario has no empires".to_string());
+    }
+
+    let number_of_empires = scenario.empires.len() as i32;
+    let owners = scenario
+        .tiles
+        .iter()
+        .filter_map(|tile| tile.owner)
+        .chain(scenario.cities.iter().map(|city| city.owner))
+        .chain(scenario.units.iter().map(|unit| unit.owner));
+    for owner in owners {
+        if owner < 0 || owner >= number_of_empires {
+            return Err(format!("Empire {} does not exist", owner));
+        }
+    }
+
+    let in_map = |location: &utils::Coordinates| match scenario.tiles.is_empty() {
+        true => {
+            let (width, height) = scenario
+                .world_size
+                .unwrap_or(config::GameSettings::default().world_size);
+            (0..width).contains(&location.x) && (0..height).contains(&location.y)
+        }
+        false => scenario
+            .tiles
+            .iter()
+            .any(|tile| tile.tile.location == *location),
+    };
+    let locations = scenario
+        .cities
+        .iter()
+        .map(|city| city.location)
+        .chain(scenario.units.iter().map(|unit| unit.location));
+    for location in locations {
+        if !in_map(&location) {
+            return Err(format!(
+                "({}, {}) is outside of the map",
+                location.x, location.y
+            ));
+        }
+    }
+
+    // Generated maps are only known once spawned, `spawn_unit` checks those
+    let mut occupancy = unit::occupancy::Occupancy::default();
+    for (index, setup) in scenario.units.iter().enumerate() {
+        let location = setup.location;
+        let on_water = scenario
+            .tiles
+            .iter()
+            .find(|tile| tile.tile.location == location)
+            .map_or(false, |tile| !tile::is_land(&tile.tile.kind));
+        if on_water {
+            return Err(format!(
+                "Unit at ({}, {}) is on water",
+                location.x, location.y
+            ));
+        }
+        if let Err(reason) = occupancy.can_enter(&location, &setup.kind) {
+            return Err(format!(
+                "Unit at ({}, {}): {}",
+                location.x, location.y, reason
+            ));
+        }
+        occupancy.add(&unit::Unit {
+            id: unit::UnitId(index as u32),
+            kind: setup.kind.clone(),
+            location,
+            ..default()
+        });
+    }
+
+    Ok(())
+}
+
+/// Ends the game once the scenario's last turn is reached
+pub fn check_turn_limit(
+    world_state: Res<world_gen::WorldState>,
+    active_scenario: Option<Res<ActiveScenario>>,
+    mut next_state: ResMut<NextState<state::AppState>>,
+) {
+    let Some(turn_limit) = active_scenario.and_then(|scenario| scenario.turn_limit) else {
+        return;
+    };
+
+    if world_state.turn >= turn_limit {
+        println!("Turn limit of {} reached", turn_limit);
+        next_state.set(state::AppState::GameOver);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ascii_map, headless};
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(name)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn saved_worlds_load_unchanged() {
+        let mut app = headless::test_game(config::GameSettings {
+            seed: 5,
+            number_of_empires: 2,
+            world_size: (60, 60),
+            ..default()
+        });
+        let path = temp_path("unciv-scenario-saved.json");
+
+        let world = &mut app.world;
+        let tiles: Vec<_> = world
+            .query::<&tile::TileComponent>()
+            .iter(world)
+            .cloned()
+            .collect();
+        let units: Vec<_> = world.query::<&unit::Unit>().iter(world).cloned().collect();
+        let mut empires = world.query::<&empire::Empire>();
+        let scenario = from_world(tiles.iter(), units.iter(), empires.iter(world), 5);
+        assert_eq!(scenario.world_size, Some((60, 60)));
+        save(&path, &scenario).unwrap();
+
+        let mut loaded = headless::test_game(config::GameSettings {
+            scenario: Some(path),
+            ..default()
+        });
+
+        assert_eq!(
+            ascii_map::render_world(&mut loaded.world),
+            ascii_map::render_world(&mut app.world)
+        );
+        assert_eq!(
+            loaded
+                .world
+                .resource::<config::GameSettings>()
+                .number_of_empires,
+            2
+        );
+    }
+
+    #[test]
+    fn hand_written_scenarios_place_empires_cities_and_units() {
+        let path = temp_path("unciv-scenario-hand-written.json");
+        let json = r#"{
+            "seed": 7,
+            "world_size": [30, 30],
+            "empires": [
+                { "name": "Rome", "inventory": { "Wood": 50 } },
+                { "name": "Carthage" }
+            ],
+            "cities": [{ "location": { "x": 17, "y": 15 }, "owner": 1, "capital": true }],
+            "units": [
+                { "kind": { "Settler": {} }, "location": { "x": 16, "y": 20 }, "owner": 0 },
+                { "kind": { "Caravan": {} }, "location": { "x": 17, "y": 20 }, "owner": 1 },
+                { "kind": { "Caravan": {} }, "location": { "x": 3, "y": 4 }, "owner": 1 }
+            ],
+            "turn_limit": 20
+        }"#;
+        std::fs::write(&path, json).unwrap();
+
+        let mut app = headless::test_game(config::GameSettings {
+            scenario: Some(path),
+            ..default()
+        });
+
+        let world = &mut app.world;
+        let mut empires: Vec<_> = world
+            .query::<&empire::Empire>()
+            .iter(world)
+            .map(|empire| (empire.name.clone(), empire.inventory.items.clone()))
+            .collect();
+        empires.sort_by_key(|(name, _)| name.clone());
+        assert_eq!(empires.len(), 2);
+        assert_eq!(empires[0].0, "Carthage");
+        assert_eq!(empires[1].1.get(&resource::Resource::Wood), Some(&50));
+
+        let mut units: Vec<_> = world
+            .query::<&unit::Unit>()
+            .iter(world)
+            .map(|unit| (unit.location, unit.owner))
+            .collect();
+        units.sort_by_key(|(location, _)| location.x);
+        // The third unit would stand in the ocean
+        assert_eq!(
+            units,
+            vec![
+                (utils::Coordinates { x: 16, y: 20 }, Some(0)),
+                (utils::Coordinates { x: 17, y: 20 }, Some(1)),
+            ]
+        );
+        let world_state = world.resource::<world_gen::WorldState>();
+        for (location, _) in units {
+            assert!(tile::is_land(&world_state.tile_data[&location].kind));
+        }
+        assert!(!tile::is_land(
+            &world_state.tile_data[&utils::Coordinates { x: 3, y: 4 }].kind
+        ));
+
+        let city = world
+            .query::<&tile::TileComponent>()
+            .iter(world)
+            .find(|tile| tile.tile.location == utils::Coordinates { x: 17, y: 15 })
+            .unwrap();
+        assert_eq!(city.owner, Some(1));
+        assert!(matches!(
+            city.building,
+            Some(building::Building::Capital(_))
+        ));
+
+        assert_eq!(world.resource::<ActiveScenario>().turn_limit, Some(20));
+        assert_eq!(
+            world.resource::<world_gen::WorldState>().tile_data.len(),
+            900
+        );
+    }
+
+    #[test]
+    fn stacked_units_are_rejected() {
+        let path = temp_path("unciv-scenario-stacked.json");
+        let json = r#"{
+            "empires": [{ "name": "Rome" }],
+            "units": [
+                { "kind": { "Settler": {} }, "location": { "x": 5, "y": 5 }, "owner": 0 },
+                { "kind": { "Caravan": {} }, "location": { "x": 5, "y": 5 }, "owner": 0 }
+            ]
+        }"#;
+        std::fs::write(&path, json).unwrap();
+
+        assert_eq!(
+            load(&path).err(),
+            Some("Unit at (5, 5): Tile is full".to_string())
+        );
+    }
+}
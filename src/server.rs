@@ -0,0 +1,448 @@
+use std::io::ErrorKind;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions, building, checksum, config, empire, headless, net::Connection, net::NetMessage,
+    replay, resource, tick, tile, triggers, unit, world_gen,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TileDiff {
+    pub location: crate::utils::Coordinates,
+    pub owner: Option<i32>,
+    pub building: Option<building::Building>,
+}
+
+/// Everything that changed since the previous diff, terrain is left out since
+/// clients generate it from the seed they are welcomed with
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StateDiff {
+    pub turn: u32,
+    pub tiles: Vec<TileDiff>,
+    pub units: Vec<unit::Unit>,
+    pub removed_units: Vec<unit::UnitId>,
+    pub inventories: Vec<(i32, Vec<(resource::Resource, i32)>)>,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+            && self.units.is_empty()
+            && self.removed_units.is_empty()
+            && self.inventories.is_empty()
+    }
+}
+
+/// Authoritative game host, clients only send actions and receive diffs
+#[derive(Resource)]
+pub struct Server {
+    listener: TcpListener,
+    /// Connections that have not sent `Join` yet
+    joining: Vec<Connection>,
+    clients: Vec<(i32, Connection)>,
+    players: i32,
+    ended_turn: HashSet<i32>,
+    turn_timer: Option<Timer>,
+    known_units: HashSet<unit::UnitId>,
+}
+
+impl Server {
+    pub fn bind(address: &str, players: i32, turn_time: Option<Duration>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Server {
+            listener,
+            joining: vec![],
+            clients: vec![],
+            players,
+            ended_turn: HashSet::new(),
+            turn_timer: turn_time.map(|duration| Timer::new(duration, TimerMode::Repeating)),
+            known_units: HashSet::new(),
+        })
+    }
+
+    pub fn local_address(&self) -> std::io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    fn send(&mut self, empire: i32, message: &NetMessage) {
+        for (client_empire, client) in self.clients.iter_mut() {
+            if *client_empire == empire {
+                if let Err(error) = client.send(message) {
+                    println!("Could not send to empire {}: {}", empire, error);
+                }
+            }
+        }
+    }
+
+    fn broadcast(&mut self, message: &NetMessage) {
+        for (empire, client) in self.clients.iter_mut() {
+            if let Err(error) = client.send(message) {
+                println!("Could not send to empire {}: {}", empire, error);
+            }
+        }
+    }
+
+    fn free_empire(&self) -> Option<i32> {
+        (0..self.players).find(|empire| self.clients.iter().all(|(taken, _)| taken != empire))
+    }
+}
+
+/// Builds the headless server app, it generates the world from `settings`
+/// and serves it on `settings.address`
+pub fn build(
+    app: &mut App,
+    settings: config::GameSettings,
+    turn_time: Option<Duration>,
+) -> std::io::Result<()> {
+    let players = settings.human_players.clamp(1, settings.number_of_empires);
+    let server = Server::bind(&settings.address, players, turn_time)?;
+
+    headless::add_plugins(app);
+    app.insert_resource(settings).insert_resource(server);
+    headless::add_new_game(app);
+    app.add_systems(
+        Update,
+        (
+            accept_clients,
+            receive_actions,
+            end_turn,
+            tick::execute_actions,
+            tick::tick_world,
+            triggers::evaluate,
+            checksum::record,
+            broadcast_diffs,
+            flush,
+        )
+            .chain(),
+    );
+
+    Ok(())
+}
+
+pub fn accept_clients(
+    mut server: ResMut<Server>,
+    settings: Res<config::GameSettings>,
+    world_state: Res<world_gen::WorldState>,
+    tile_query: Query<&tile::TileComponent>,
+    unit_query: Query<&unit::Unit>,
+    empire_query: Query<&empire::Empire>,
+) {
+    loop {
+        match server.listener.accept() {
+            Ok((stream, address)) => match Connection::new(stream) {
+                Ok(connection) => {
+                    println!("Connection from {}", address);
+                    server.joining.push(connection);
+                }
+                Err(error) => println!("Could not accept {}: {}", address, error),
+            },
+            Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+            Err(error) => {
+                println!("Could not accept client: {}", error);
+                break;
+            }
+        }
+    }
+
+    let mut still_joining = vec![];
+    for mut connection in std::mem::take(&mut server.joining) {
+        let joined = match connection.poll() {
+            Ok(messages) => messages
+                .iter()
+                .any(|message| matches!(message, NetMessage::Join)),
+            Err(error) => {
+                println!("Lost joining client: {}", error);
+                continue;
+            }
+        };
+        if !joined {
+            still_joining.push(connection);
+            continue;
+        }
+
+        let Some(empire) = server.free_empire() else {
+            let _ = connection.send(&NetMessage::Rejected {
+                reason: "Game is full".to_string(),
+            });
+            continue;
+        };
+
+        let welcome = NetMessage::Welcome {
+            empire,
+            header: replay::ReplayHeader {
+                seed: settings.seed,
+                number_of_empires: settings.number_of_empires,
+                human_players: server.players,
+                world_size: settings.world_size,
+                map_type: settings.map_type,
+                starting_map: settings.starting_map.clone(),
+                scenario: settings.scenario.clone(),
+            },
+        };
+        let snapshot = NetMessage::Diff(StateDiff {
+            turn: world_state.turn,
+            tiles: tile_query
+                .iter()
+                .filter(|tile| tile.owner.is_some() || tile.building.is_some())
+                .map(tile_diff)
+                .collect(),
+            units: unit_query.iter().cloned().collect(),
+            removed_units: vec![],
+            inventories: empire_query.iter().map(inventory).collect(),
+        });
+
+        if connection.send(&welcome).is_ok() && connection.send(&snapshot).is_ok() {
+            println!("Empire {} joined", empire);
+            server.clients.push((empire, connection));
+        }
+    }
+    server.joining = still_joining;
+}
+
+/// Validates the actions sent by clients, they are always executed as the
+/// empire the sender was assigned. Illegal actions are answered with
+/// `NetMessage::Rejected`
+pub fn receive_actions(
+    mut server: ResMut<Server>,
+    mut action_writer: EventWriter<tick::ActionEvent>,
+    world_state: Res<world_gen::WorldState>,
+    tile_query: Query<&mut tile::TileComponent>,
+    unit_query: Query<(Entity, &Transform, &mut unit::Unit)>,
+    empire_query: Query<&mut empire::Empire>,
+) {
+    let mut received = vec![];
+    server
+        .clients
+        .retain_mut(|(empire, client)| match client.poll() {
+            Ok(messages) => {
+                received.extend(messages.into_iter().map(|message| (*empire, message)));
+                true
+            }
+            Err(error) => {
+                println!("Empire {} disconnected: {}", empire, error);
+                false
+            }
+        });
+
+    for (empire, message) in received {
+        let NetMessage::Action { action } = message else {
+            continue;
+        };
+
+        if let actions::Action::EndTurn = action {
+            server.ended_turn.insert(empire);
+            continue;
+        }
+
+        match actions::validate(
+            &action,
+            empire,
+            &tile_query,
+            &unit_query,
+            &empire_query,
+            &world_state,
+        ) {
+            Ok(()) => {
+                action_writer.send(tick::ActionEvent {
+                    action,
+                    empire,
+                    source: tick::ActionSource::Network,
+                });
+            }
+            Err(reason) => server.send(empire, &NetMessage::Rejected { reason }),
+        }
+    }
+}
+
+/// The turn advances once every connected player ended it, or when the turn
+/// timer runs out
+pub fn end_turn(
+    time: Res<Time>,
+    mut server: ResMut<Server>,
+    mut action_writer: EventWriter<tick::ActionEvent>,
+) {
+    let timer_finished = match &mut server.turn_timer {
+        Some(timer) => timer.tick(time.delta()).just_finished(),
+        None => false,
+    };
+    let all_ended = !server.clients.is_empty()
+        && server
+            .clients
+            .iter()
+            .all(|(empire, _)| server.ended_turn.contains(empire));
+
+    if !timer_finished && !all_ended {
+        return;
+    }
+
+    server.ended_turn.clear();
+    if let Some(timer) = &mut server.turn_timer {
+        timer.reset();
+    }
+
+    // Every player ends their turn in order so `hotseat::TurnOrder` ticks once
+    for empire in 0..server.players {
+        action_writer.send(tick::ActionEvent {
+            action: actions::Action::EndTurn,
+            empire,
+            source: tick::ActionSource::Network,
+        });
+    }
+}
+
+pub fn broadcast_diffs(
+    mut server: ResMut<Server>,
+    world_state: Res<world_gen::WorldState>,
+    tile_query: Query<&tile::TileComponent, Changed<tile::TileComponent>>,
+    unit_query: Query<&unit::Unit, Changed<unit::Unit>>,
+    empire_query: Query<&empire::Empire, Changed<empire::Empire>>,
+) {
+    let current_units: HashSet<unit::UnitId> = world_state.units.keys().copied().collect();
+    let removed_units = server
+        .known_units
+        .difference(&current_units)
+        .copied()
+        .collect();
+    server.known_units = current_units;
+
+    if server.clients.is_empty() {
+        return;
+    }
+
+    let diff = StateDiff {
+        turn: world_state.turn,
+        tiles: tile_query.iter().map(tile_diff).collect(),
+        units: unit_query.iter().cloned().collect(),
+        removed_units,
+        inventories: empire_query.iter().map(inventory).collect(),
+    };
+
+    if !diff.is_empty() {
+        server.broadcast(&NetMessage::Diff(diff));
+    }
+}
+
+/// Sends what the clients' sockets did not take earlier
+pub fn flush(mut server: ResMut<Server>) {
+    for (empire, client) in server.clients.iter_mut() {
+        if let Err(error) = client.flush() {
+            println!("Could not send to empire {}: {}", empire, error);
+        }
+    }
+}
+
+fn tile_diff(tile: &tile::TileComponent) -> TileDiff {
+    TileDiff {
+        location: tile.tile.location,
+        owner: tile.owner,
+        building: tile.building.clone(),
+    }
+}
+
+fn inventory(empire: &empire::Empire) -> (i32, Vec<(resource::Resource, i32)>) {
+    (
+        empire.id,
+        empire.inventory.items.clone().into_iter().collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpStream;
+
+    /// Updates the server until the client received a matching message
+    fn run_until(
+        app: &mut App,
+        client: &mut Connection,
+        received: &mut Vec<NetMessage>,
+        done: impl Fn(&NetMessage) -> bool,
+    ) {
+        for _ in 0..100 {
+            app.update();
+            std::thread::sleep(Duration::from_millis(5));
+            received.extend(client.poll().unwrap());
+            if received.iter().any(&done) {
+                return;
+            }
+        }
+        panic!("Server did not respond");
+    }
+
+    #[test]
+    fn stand_in_client_plays_a_turn() {
+        let mut app = App::new();
+        let settings = config::GameSettings {
+            seed: 7,
+            number_of_empires: 2,
+            human_players: 1,
+            world_size: (60, 60),
+            address: "127.0.0.1:0".to_string(),
+            ..default()
+        };
+        build(&mut app, settings, None).unwrap();
+        app.update();
+
+        let address = app.world.resource::<Server>().local_address().unwrap();
+        let mut client = Connection::new(TcpStream::connect(address).unwrap()).unwrap();
+        client.send(&NetMessage::Join).unwrap();
+
+        let mut received = vec![];
+        run_until(&mut app, &mut client, &mut received, |message| {
+            matches!(message, NetMessage::Welcome { empire: 0, .. })
+        });
+
+        // Another empire's unit, a city on the capital and a target off the map
+        let illegal = [
+            actions::Action::KillUnit(unit::UnitId(1)),
+            actions::Action::FoundCity(unit::UnitId(0)),
+            actions::Action::MoveUnit(actions::MoveUnit {
+                unit: unit::UnitId(0),
+                target: crate::utils::Coordinates { x: -5, y: 0 },
+            }),
+        ];
+        for action in illegal {
+            client.send(&NetMessage::Action { action }).unwrap();
+        }
+        client
+            .send(&NetMessage::Action {
+                action: actions::Action::EndTurn,
+            })
+            .unwrap();
+
+        run_until(
+            &mut app,
+            &mut client,
+            &mut received,
+            |message| matches!(message, NetMessage::Diff(diff) if diff.turn == 1),
+        );
+
+        let reasons: Vec<_> = received
+            .iter()
+            .filter_map(|message| match message {
+                NetMessage::Rejected { reason } => Some(reason.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "Unit belongs to another empire",
+                "Can't build over a building",
+                "Target is outside of the map",
+            ]
+        );
+        assert_eq!(app.world.resource::<world_gen::WorldState>().turn, 1);
+        assert!(app
+            .world
+            .resource::<world_gen::WorldState>()
+            .units
+            .contains_key(&unit::UnitId(0)));
+    }
+}
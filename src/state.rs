@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+
+use crate::config::CONFIG;
+use crate::{controls, empire, hotseat, scenario, tile, unit, world_gen};
+
+#[derive(States, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AppState {
+    #[default]
+    MainMenu,
+    /// Waiting for the players of a network game to connect
+    Lobby,
+    Loading,
+    InGame,
+    /// Hides the map while the next hotseat player takes the seat
+    PassTurn,
+    Paused,
+    /// The game stands still while the map editor changes it
+    Editor,
+    GameOver,
+}
+
+/// Marker for entities that belong to a running game and are removed when
+/// returning to the main menu
+#[derive(Component)]
+pub struct GameEntity;
+
+pub fn finish_loading(mut next_state: ResMut<NextState<AppState>>) {
+    next_state.set(AppState::InGame);
+}
+
+pub fn toggle_pause(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !keyboard_input.just_pressed(CONFIG.keys.pause) {
+        return;
+    }
+
+    match state.get() {
+        AppState::InGame => next_state.set(AppState::Paused),
+        AppState::Paused => next_state.set(AppState::InGame),
+        _ => (),
+    }
+}
+
+/// The local player has lost once their empire owns no tiles and no units
+pub fn check_game_over(
+    mut next_state: ResMut<NextState<AppState>>,
+    selector_state: Res<controls::SelectorState>,
+    tile_query: Query<&tile::TileComponent>,
+    unit_query: Query<&unit::Unit>,
+) {
+    let local_empire = Some(selector_state.local_empire);
+
+    let owns_tile = tile_query.iter().any(|tile| tile.owner == local_empire);
+    let owns_unit = unit_query.iter().any(|unit| unit.owner == local_empire);
+
+    if !owns_tile && !owns_unit {
+        next_state.set(AppState::GameOver);
+    }
+}
+
+pub fn cleanup_game(
+    mut commands: Commands,
+    query: Query<
+        Entity,
+        Or<(
+            With<GameEntity>,
+            With<tile::TileComponent>,
+            With<unit::Unit>,
+            With<empire::Empire>,
+        )>,
+    >,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands.remove_resource::<world_gen::WorldState>();
+    commands.remove_resource::<controls::SelectorState>();
+    commands.remove_resource::<hotseat::TurnOrder>();
+    commands.remove_resource::<scenario::ActiveScenario>();
+}
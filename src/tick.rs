@@ -1,21 +1,82 @@
 use bevy::prelude::*;
 
-use crate::{actions, building, controls, empire, resource, tile, unit, world_gen};
+use crate::{
+    actions, building, checksum, controls, empire, hotseat, net, replay, resource, state, tile,
+    undo, unit, utils, world_gen,
+};
 
 fn tick_units(
     mut commands: Commands,
     mut units: Query<(Entity, &Transform, &mut unit::Unit)>,
-    world_state: &Res<world_gen::WorldState>,
+    tile_query: &mut Query<&mut tile::TileComponent>,
+    empire_query: &mut Query<&mut empire::Empire>,
+    selector_state: &mut controls::SelectorState,
+    world_state: &mut world_gen::WorldState,
+    building_resources: &Res<building::BuildingResources>,
     time: &Res<Time>,
 ) {
+    let positions: Vec<(utils::Coordinates, Option<i32>)> = units
+        .iter()
+        .map(|(_, _, unit)| (unit.location, unit.owner))
+        .collect();
+
+    for (location, owner) in positions.iter() {
+        let empire_entity = owner.and_then(|owner| world_state.empires.get(&owner));
+        if let Some(mut empire) =
+            empire_entity.and_then(|entity| empire_query.get_mut(*entity).ok())
+        {
+            empire.reveal(*location);
+        }
+    }
+    let no_tiles = Default::default();
+
     for res in units.iter_mut() {
         let (unit_entity, transform, mut unit) = res;
 
+        let enemy_near = positions.iter().any(|(location, owner)| {
+            *owner != unit.owner
+                && unit::orders::is_near(location, &unit.location, unit::orders::SENTRY_RANGE)
+        });
+        let explored = unit
+            .owner
+            .and_then(|owner| world_state.empires.get(&owner))
+            .and_then(|empire_entity| empire_query.get(*empire_entity).ok())
+            .map_or(&no_tiles, |empire| &empire.explored);
+        let plan = unit::orders::plan(&mut unit, enemy_near, &world_state.tile_data, explored);
+
+        if plan == unit::orders::Plan::FoundCity {
+            let founded = unit
+                .owner
+                .ok_or("Unit has no owner".to_string())
+                .and_then(|owner| {
+                    actions::found_city(
+                        &unit,
+                        unit_entity,
+                        owner,
+                        tile_query,
+                        &mut commands,
+                        world_state,
+                        selector_state,
+                        building_resources,
+                    )
+                });
+            match founded {
+                Ok(()) => continue,
+                Err(reason) => {
+                    println!("Unit {} can't found a city: {}", unit.id.0, reason);
+                    unit.orders.clear();
+                }
+            }
+        }
+
         if let Some(target) = &unit.target {
             if target == &unit.location {
                 unit.target = None;
             } else {
                 let next_location = unit::next_location(&unit, &world_state);
+                if unit::crosses_river(world_state, &unit.location, &next_location) {
+                    unit.skip_next_move = true;
+                }
 
                 (commands, unit) = unit::next_location_update(
                     commands,
@@ -23,12 +84,13 @@ fn tick_units(
                     time,
                     transform,
                     &unit_entity,
-                    &world_state,
+                    world_state,
                     &next_location,
                 );
             }
         }
-        unit.moved = false;
+        // Crossing a river takes the move of the next turn as well
+        unit.moved = std::mem::take(&mut unit.skip_next_move);
     }
 }
 
@@ -37,42 +99,185 @@ pub fn execute_actions(
     mut commands: Commands,
     mut selector_state: ResMut<controls::SelectorState>,
     mut tile_query: Query<&mut tile::TileComponent>,
+    mut unit_query: Query<(Entity, &Transform, &mut unit::Unit)>,
     mut end_turn_writer: EventWriter<EndTurnEvent>,
     building_resources: Res<building::BuildingResources>,
     unit_resources: Res<unit::UnitResources>,
-    world_state: Res<world_gen::WorldState>,
+    mut world_state: ResMut<world_gen::WorldState>,
+    time: Res<Time>,
+    mut next_state: ResMut<NextState<state::AppState>>,
+    (mut action_log, mut turn_order, mut net_session, state_hashes, mut undo_stack): (
+        Option<ResMut<replay::ActionLog>>,
+        Option<ResMut<hotseat::TurnOrder>>,
+        Option<ResMut<net::NetSession>>,
+        Option<Res<checksum::StateHashes>>,
+        Option<ResMut<undo::UndoStack>>,
+    ),
+    mut empire_query: Query<&mut empire::Empire>,
 ) {
+    // Actions of one empire read in the same frame are undone together
+    let mut batch_empire = None;
+
     for action_event in action_reader.read() {
-        (tile_query, selector_state, commands, end_turn_writer) = actions::execute(
-            action_event.action.clone(),
+        let action = actions::resolve(action_event.action.clone(), &selector_state, &tile_query);
+
+        // Network games only execute local orders once every peer sent theirs
+        if let Some(net_session) = net_session.as_mut() {
+            match action_event.source {
+                ActionSource::Local | ActionSource::Bot => {
+                    let state_hash = state_hashes
+                        .as_ref()
+                        .and_then(|state_hashes| state_hashes.get(world_state.turn));
+                    net::queue_local(net_session, action_event.empire, action, state_hash);
+                    continue;
+                }
+                ActionSource::Network | ActionSource::Scripted => (),
+            }
+        }
+
+        // In hotseat games the world only ticks once every player ended their turn
+        if let (actions::Action::EndTurn, Some(turn_order)) = (&action, turn_order.as_mut()) {
+            let round_complete = match turn_order.end_turn(action_event.empire) {
+                Ok(round_complete) => round_complete,
+                Err(reason) => {
+                    println!("Rejected action: {}", reason);
+                    continue;
+                }
+            };
+
+            // Only players sharing this machine need to hand over the seat
+            let pass_seat = match action_event.source {
+                ActionSource::Local => turn_order.players.len() > 1,
+                ActionSource::Bot | ActionSource::Network | ActionSource::Scripted => false,
+            };
+            if pass_seat {
+                next_state.set(state::AppState::PassTurn);
+            }
+            if !round_complete {
+                replay::record(&mut action_log, &world_state, action_event.empire, &action);
+
+                // Their turn ended, so it can't be taken back by the next player
+                if let Some(undo_stack) = undo_stack.as_mut() {
+                    undo_stack.clear_empire(action_event.empire);
+                }
+                batch_empire = None;
+                continue;
+            }
+        }
+
+        if let Some(undo_stack) = undo_stack.as_mut() {
+            match &action {
+                actions::Action::Undo => {
+                    match undo_stack.pop(action_event.empire) {
+                        Some(entry) => {
+                            replay::record(
+                                &mut action_log,
+                                &world_state,
+                                action_event.empire,
+                                &action,
+                            );
+                            undo::restore(
+                                entry,
+                                &mut commands,
+                                &mut tile_query,
+                                &mut unit_query,
+                                &mut empire_query,
+                                &mut selector_state,
+                                &mut world_state,
+                                &building_resources,
+                                &unit_resources,
+                                &time,
+                            );
+                        }
+                        None => println!("Nothing to undo"),
+                    }
+                    batch_empire = None;
+                    continue;
+                }
+                // Rounds that ended can't be taken back
+                actions::Action::EndTurn => {
+                    undo_stack.clear();
+                    batch_empire = None;
+                }
+                action => {
+                    if let Some(entry) = undo::snapshot(
+                        action,
+                        action_event.empire,
+                        &world_state,
+                        &tile_query,
+                        &unit_query,
+                        &empire_query,
+                    ) {
+                        if batch_empire == Some(action_event.empire) {
+                            undo_stack.merge(entry);
+                        } else {
+                            undo_stack.push(entry);
+                        }
+                        batch_empire = Some(action_event.empire);
+                    }
+                }
+            }
+        }
+
+        let accepted;
+        (
             tile_query,
+            unit_query,
+            selector_state,
+            commands,
+            end_turn_writer,
+            accepted,
+        ) = actions::execute(
+            action.clone(),
+            action_event.empire,
+            tile_query,
+            unit_query,
             selector_state,
             commands,
             end_turn_writer,
             &building_resources,
             &unit_resources,
-            &world_state,
+            &mut world_state,
+            &mut empire_query,
+            &time,
         );
+
+        // Only actions that changed the game are replayed
+        if accepted {
+            replay::record(&mut action_log, &world_state, action_event.empire, &action);
+        }
     }
 }
 
 pub fn tick_world(
     commands: Commands,
-    mut tile_query: Query<&tile::TileComponent>,
+    mut tile_query: Query<&mut tile::TileComponent>,
     mut empire_query: Query<&mut empire::Empire>,
     mut end_turn_reader: EventReader<EndTurnEvent>,
-    world_state: Res<world_gen::WorldState>,
+    mut world_state: ResMut<world_gen::WorldState>,
     unit_query: Query<(Entity, &Transform, &mut unit::Unit)>,
+    mut selector_state: ResMut<controls::SelectorState>,
+    building_resources: Res<building::BuildingResources>,
     time: Res<Time>,
 ) {
     for _ in end_turn_reader.read() {
-        tick_units(commands, unit_query, &world_state, &time);
+        tick_units(
+            commands,
+            unit_query,
+            &mut tile_query,
+            &mut empire_query,
+            &mut selector_state,
+            &mut world_state,
+            &building_resources,
+            &time,
+        );
 
-        for tile in tile_query.iter_mut() {
+        for tile in tile_query.iter() {
             if let Some(owner) = tile.owner {
                 let owner_entity = world_state.empires.get(&owner).unwrap();
                 let mut empire: Mut<'_, empire::Empire> =
                     empire_query.get_mut(owner_entity.clone()).unwrap();
+                empire.reveal(tile.tile.location);
 
                 if let Some(building) = &tile.building {
                     let production = building::building_production(building);
@@ -81,22 +286,22 @@ pub fn tick_world(
                     }
                 }
 
-                match tile.tile.kind {
-                    tile::TileKind::Forest => {
-                        add_item(empire, resource::Resource::Wood, 1);
-                    }
-                    tile::TileKind::Mountain => {
-                        add_item(empire, resource::Resource::Stone, 1);
-                    }
-                    _ => (),
+                if tile.tile.river {
+                    empire = add_item(empire, resource::Resource::Food, 1);
+                }
+
+                for (resource, amount) in tile::tile_yield(&tile.tile.kind) {
+                    empire = add_item(empire, resource, amount);
                 }
             }
         }
+
+        world_state.turn += 1;
         break;
     }
 }
 
-fn add_item<'a>(
+pub fn add_item<'a>(
     mut empire: Mut<'a, empire::Empire>,
     item: resource::Resource,
     amount: i32,
@@ -109,6 +314,22 @@ fn add_item<'a>(
 #[derive(Event)]
 pub struct ActionEvent {
     pub action: actions::Action,
+    pub empire: i32,
+    pub source: ActionSource,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ActionSource {
+    /// Issued by the player on this machine
+    Local,
+    /// Issued by a bot connected to this machine
+    Bot,
+    /// Agreed on by all peers of a network game
+    Network,
+    /// Issued by a program driving this game directly, like a training
+    /// environment or a replay. Executed right away, it never goes to peers or passes
+    /// the seat on
+    Scripted,
 }
 
 #[derive(Event)]
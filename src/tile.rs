@@ -1,10 +1,11 @@
 use bevy::{prelude::*, utils::HashMap};
 
 use bevy_mod_picking::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::{building, colors, controls, empire, utils};
+use crate::{building, colors, controls, empire, resource, utils};
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum TileKind {
     Desert,
     Forest,
@@ -13,8 +14,32 @@ pub enum TileKind {
     Shallows,
     Ocean,
     Beach,
+    Grassland,
+    Plains,
+    Tundra,
+    Snow,
+    Jungle,
+    Swamp,
+    Hills,
 }
 
+pub const TILE_KINDS: [TileKind; 14] = [
+    TileKind::Ocean,
+    TileKind::Shallows,
+    TileKind::Beach,
+    TileKind::Desert,
+    TileKind::Grassland,
+    TileKind::Plains,
+    TileKind::Forest,
+    TileKind::Jungle,
+    TileKind::Swamp,
+    TileKind::Tundra,
+    TileKind::Snow,
+    TileKind::Hills,
+    TileKind::Mountain,
+    TileKind::SnowyMountain,
+];
+
 pub fn tile_material(kind: &TileKind, tile_resources: &TileResources) -> Handle<StandardMaterial> {
     match kind {
         TileKind::Desert => tile_resources.materials.desert.clone(),
@@ -24,6 +49,13 @@ pub fn tile_material(kind: &TileKind, tile_resources: &TileResources) -> Handle<
         TileKind::Shallows => tile_resources.materials.water.clone(),
         TileKind::Ocean => tile_resources.materials.ocean.clone(),
         TileKind::Beach => tile_resources.materials.beach.clone(),
+        TileKind::Grassland => tile_resources.materials.grassland.clone(),
+        TileKind::Plains => tile_resources.materials.plains.clone(),
+        TileKind::Tundra => tile_resources.materials.tundra.clone(),
+        TileKind::Snow => tile_resources.materials.snow.clone(),
+        TileKind::Jungle => tile_resources.materials.jungle.clone(),
+        TileKind::Swamp => tile_resources.materials.swamp.clone(),
+        TileKind::Hills => tile_resources.materials.hills.clone(),
     }
 }
 
@@ -36,21 +68,43 @@ pub fn tile_string(kind: &TileKind) -> String {
         TileKind::Shallows => "Water".to_string(),
         TileKind::Ocean => "Ocean".to_string(),
         TileKind::Beach => "Beach".to_string(),
+        TileKind::Grassland => "Grassland".to_string(),
+        TileKind::Plains => "Plains".to_string(),
+        TileKind::Tundra => "Tundra".to_string(),
+        TileKind::Snow => "Snow".to_string(),
+        TileKind::Jungle => "Jungle".to_string(),
+        TileKind::Swamp => "Swamp".to_string(),
+        TileKind::Hills => "Hills".to_string(),
+    }
+}
+
+/// What an owned tile adds to its empire's inventory every turn
+pub fn tile_yield(kind: &TileKind) -> Vec<(resource::Resource, i32)> {
+    match kind {
+        TileKind::Forest | TileKind::Jungle => vec![(resource::Resource::Wood, 1)],
+        TileKind::Mountain | TileKind::Hills => vec![(resource::Resource::Stone, 1)],
+        TileKind::Grassland | TileKind::Swamp => vec![(resource::Resource::Food, 1)],
+        TileKind::Plains => vec![(resource::Resource::Food, 1), (resource::Resource::Wood, 1)],
+        _ => vec![],
     }
 }
 
-#[derive(Component, Clone)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct TileComponent {
     pub owner: Option<i32>,
     pub building: Option<building::Building>,
     pub tile: Tile,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Tile {
     pub location: utils::Coordinates,
     pub kind: TileKind,
     pub height: f32,
+    /// Rivers run through land tiles down to the sea
+    pub river: bool,
+    /// Landmass or water body of the tile, see `geography::Geography`
+    pub body: u32,
 }
 
 struct TileMaterials {
@@ -61,6 +115,14 @@ struct TileMaterials {
     pub water: Handle<StandardMaterial>,
     pub ocean: Handle<StandardMaterial>,
     pub beach: Handle<StandardMaterial>,
+    pub grassland: Handle<StandardMaterial>,
+    pub plains: Handle<StandardMaterial>,
+    pub tundra: Handle<StandardMaterial>,
+    pub snow: Handle<StandardMaterial>,
+    pub jungle: Handle<StandardMaterial>,
+    pub swamp: Handle<StandardMaterial>,
+    pub hills: Handle<StandardMaterial>,
+    pub river: Handle<StandardMaterial>,
     pub empire_colors: HashMap<i32, Handle<StandardMaterial>>,
 }
 
@@ -87,6 +149,14 @@ pub fn create_tile_resources<'a, 'b>(
     let water = materials.add(colors::plastic_material(200.0, 0.3, 0.5));
     let ocean = materials.add(colors::plastic_material(200.0, 0.4, 0.3));
     let beach = materials.add(colors::plastic_material(47., 0.29, 0.49));
+    let grassland = materials.add(colors::plastic_material(100., 0.6, 0.4));
+    let plains = materials.add(colors::plastic_material(60., 0.45, 0.45));
+    let tundra = materials.add(colors::plastic_material(90., 0.15, 0.5));
+    let snow = materials.add(colors::plastic_material(0., 0., 0.9));
+    let jungle = materials.add(colors::plastic_material(130., 0.8, 0.25));
+    let swamp = materials.add(colors::plastic_material(80., 0.35, 0.25));
+    let hills = materials.add(colors::plastic_material(35., 0.35, 0.35));
+    let river = materials.add(colors::plastic_material(190.0, 0.6, 0.55));
 
     let square: Handle<Mesh> = meshes.add(Cuboid::new(TILE_SIZE, TILE_SIZE, TILE_SIZE));
 
@@ -111,6 +181,14 @@ pub fn create_tile_resources<'a, 'b>(
                 empire_colors,
                 ocean,
                 beach,
+                grassland,
+                plains,
+                tundra,
+                snow,
+                jungle,
+                swamp,
+                hills,
+                river,
             },
             square,
         },
@@ -121,14 +199,25 @@ pub fn create_tile_resources<'a, 'b>(
 
 pub fn is_spawnable(kind: &TileKind) -> bool {
     match kind {
-        TileKind::Forest | TileKind::Mountain => true,
+        TileKind::Forest
+        | TileKind::Mountain
+        | TileKind::Grassland
+        | TileKind::Plains
+        | TileKind::Hills => true,
         _ => false,
     }
 }
 
 pub fn is_settleable(kind: &TileKind) -> bool {
     match kind {
-        TileKind::Desert | TileKind::Forest | TileKind::Mountain => true,
+        TileKind::Desert
+        | TileKind::Forest
+        | TileKind::Mountain
+        | TileKind::Grassland
+        | TileKind::Plains
+        | TileKind::Tundra
+        | TileKind::Jungle
+        | TileKind::Hills => true,
         _ => false,
     }
 }
@@ -140,6 +229,20 @@ pub fn is_land(kind: &TileKind) -> bool {
     }
 }
 
+/// Owned tiles show their empire's color, the others their kind
+pub fn material(tile_resources: &TileResources, tile: &TileComponent) -> Handle<StandardMaterial> {
+    match tile.owner {
+        Some(empire_id) => tile_resources
+            .materials
+            .empire_colors
+            .get(&(empire_id % 10))
+            .unwrap()
+            .clone(),
+        None if tile.tile.river => tile_resources.materials.river.clone(),
+        None => tile_material(&tile.tile.kind, tile_resources),
+    }
+}
+
 pub fn make_bundle(
     tile_resources: &Res<TileResources>,
     tile: &TileComponent,
@@ -150,15 +253,7 @@ pub fn make_bundle(
     On<Pointer<Drag>>,
     On<Pointer<Click>>,
 ) {
-    let material = match tile.owner {
-        Some(empire_id) => tile_resources
-            .materials
-            .empire_colors
-            .get(&(empire_id % 10))
-            .unwrap()
-            .clone(),
-        None => tile_material(&tile.tile.kind, &tile_resources),
-    };
+    let material = material(tile_resources, tile);
 
     let mut tile_location = utils::to_transform(&tile.tile.location);
 
@@ -0,0 +1,244 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{empire, resource, scenario, state, tick, tile, unit, utils, world_gen};
+
+/// Fires its effects the first turn its condition holds, every such turn
+/// when `repeat` is set
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trigger {
+    pub when: Condition,
+    pub then: Vec<Effect>,
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Condition {
+    TurnReached(u32),
+    TileOwned {
+        location: utils::Coordinates,
+        empire: i32,
+    },
+    ResourceAtLeast {
+        empire: i32,
+        resource: resource::Resource,
+        amount: i32,
+    },
+    /// A unit of the empire stands inside the rectangle, corners included
+    UnitInRegion {
+        empire: i32,
+        min: utils::Coordinates,
+        max: utils::Coordinates,
+    },
+    All(Vec<Condition>),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Effect {
+    SpawnUnit(scenario::UnitSetup),
+    GrantResource {
+        empire: i32,
+        resource: resource::Resource,
+        amount: i32,
+    },
+    Message(String),
+    Victory(i32),
+}
+
+fn holds(
+    condition: &Condition,
+    world_state: &world_gen::WorldState,
+    tile_query: &Query<&tile::TileComponent>,
+    unit_query: &Query<&unit::Unit>,
+    empire_query: &Query<&mut empire::Empire>,
+) -> bool {
+    match condition {
+        Condition::TurnReached(turn) => world_state.turn >= *turn,
+        Condition::TileOwned { location, empire } => world_state
+            .tile_entities
+            .get(location)
+            .and_then(|tile_entity| tile_query.get(*tile_entity).ok())
+            .map_or(false, |tile| tile.owner == Some(*empire)),
+        Condition::ResourceAtLeast {
+            empire,
+            resource,
+            amount,
+        } => world_state
+            .empires
+            .get(empire)
+            .and_then(|empire_entity| empire_query.get(*empire_entity).ok())
+            .map_or(false, |empire| {
+                empire.inventory.items.get(resource).unwrap_or(&0) >= amount
+            }),
+        Condition::UnitInRegion { empire, min, max } => unit_query.iter().any(|unit| {
+            unit.owner == Some(*empire)
+                && (min.x..=max.x).contains(&unit.location.x)
+                && (min.y..=max.y).contains(&unit.location.y)
+        }),
+        Condition::All(conditions) => conditions
+            .iter()
+            .all(|condition| holds(condition, world_state, tile_query, unit_query, empire_query)),
+    }
+}
+
+/// Runs once at the start of the game and after every turn, triggers fire in
+/// the order the scenario lists them so every peer and replay sees the same
+/// effects
+pub fn evaluate(
+    mut commands: Commands,
+    active_scenario: Option<ResMut<scenario::ActiveScenario>>,
+    mut world_state: ResMut<world_gen::WorldState>,
+    tile_query: Query<&tile::TileComponent>,
+    unit_query: Query<&unit::Unit>,
+    mut empire_query: Query<&mut empire::Empire>,
+    unit_resources: Res<unit::UnitResources>,
+    mut next_state: ResMut<NextState<state::AppState>>,
+) {
+    let Some(mut active_scenario) = active_scenario else {
+        return;
+    };
+    if active_scenario.evaluated_turn == Some(world_state.turn) {
+        return;
+    }
+    active_scenario.evaluated_turn = Some(world_state.turn);
+
+    let mut effects = vec![];
+    active_scenario.triggers.retain(|trigger| {
+        if !holds(
+            &trigger.when,
+            &world_state,
+            &tile_query,
+            &unit_query,
+            &empire_query,
+        ) {
+            return true;
+        }
+
+        effects.extend(trigger.then.iter().cloned());
+        trigger.repeat
+    });
+
+    for effect in effects {
+        match effect {
+            Effect::SpawnUnit(setup) => {
+                if let Err(reason) =
+                    scenario::spawn_unit(setup, &mut commands, &mut world_state, &unit_resources)
+                {
+                    println!("Trigger can't spawn unit: {}", reason);
+                }
+            }
+            Effect::GrantResource {
+                empire,
+                resource,
+                amount,
+            } => {
+                let Some(empire_entity) = world_state.empires.get(&empire) else {
+                    continue;
+                };
+                if let Ok(empire) = empire_query.get_mut(*empire_entity) {
+                    tick::add_item(empire, resource, amount);
+                }
+            }
+            Effect::Message(message) => {
+                println!("{}", message);
+                active_scenario.messages.push(message);
+            }
+            Effect::Victory(empire) => {
+                println!("Empire {} won the scenario", empire);
+                active_scenario.winner = Some(empire);
+                next_state.set(state::AppState::GameOver);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{actions, config, headless};
+
+    #[test]
+    fn triggers_fire_once_their_condition_holds() {
+        let path = std::env::temp_dir()
+            .join("unciv-scenario-triggers.json")
+            .to_string_lossy()
+            .to_string();
+        let json = r#"{
+            "seed": 7,
+            "world_size": [30, 30],
+            "empires": [{ "name": "Rome" }, { "name": "Carthage" }],
+            "units": [{ "kind": { "Settler": {} }, "location": { "x": 16, "y": 20 }, "owner": 0 }],
+            "triggers": [
+                {
+                    "when": { "TurnReached": 0 },
+                    "then": [{ "Message": "Carthage must be defended" }]
+                },
+                {
+                    "when": { "TurnReached": 2 },
+                    "then": [
+                        { "GrantResource": { "empire": 1, "resource": "Stone", "amount": 5 } },
+                        { "Message": "Reinforcements arrived" },
+                        { "SpawnUnit": { "kind": { "Caravan": {} }, "location": { "x": 18, "y": 20 }, "owner": 1 } }
+                    ]
+                },
+                {
+                    "when": { "UnitInRegion": { "empire": 0, "min": { "x": 15, "y": 19 }, "max": { "x": 17, "y": 21 } } },
+                    "then": [{ "GrantResource": { "empire": 0, "resource": "Wood", "amount": 1 } }],
+                    "repeat": true
+                },
+                {
+                    "when": { "All": [{ "TurnReached": 3 }, { "ResourceAtLeast": { "empire": 1, "resource": "Stone", "amount": 5 } }] },
+                    "then": [{ "Victory": 1 }]
+                }
+            ]
+        }"#;
+        std::fs::write(&path, json).unwrap();
+
+        let mut app = headless::test_game(config::GameSettings {
+            scenario: Some(path),
+            ..default()
+        });
+
+        let wood = |app: &mut App, name: &str| {
+            app.world
+                .query::<&empire::Empire>()
+                .iter(&app.world)
+                .find(|empire| empire.name == name)
+                .and_then(|empire| empire.inventory.items.get(&resource::Resource::Wood))
+                .copied()
+        };
+
+        // Conditions that hold from the start fire before the first turn ends
+        assert_eq!(wood(&mut app, "Rome"), Some(1));
+        assert_eq!(
+            app.world.resource::<scenario::ActiveScenario>().messages,
+            vec!["Carthage must be defended"]
+        );
+
+        headless::send(&mut app, 0, actions::Action::EndTurn);
+        assert_eq!(wood(&mut app, "Rome"), Some(2));
+        assert_eq!(
+            app.world
+                .resource::<scenario::ActiveScenario>()
+                .messages
+                .len(),
+            1
+        );
+
+        headless::send(&mut app, 0, actions::Action::EndTurn);
+        assert_eq!(wood(&mut app, "Rome"), Some(3));
+        let active_scenario = app.world.resource::<scenario::ActiveScenario>();
+        assert_eq!(
+            active_scenario.messages,
+            vec!["Carthage must be defended", "Reinforcements arrived"]
+        );
+        assert_eq!(active_scenario.winner, None);
+        assert_eq!(app.world.query::<&unit::Unit>().iter(&app.world).len(), 2);
+
+        headless::send(&mut app, 0, actions::Action::EndTurn);
+        let active_scenario = app.world.resource::<scenario::ActiveScenario>();
+        assert_eq!(active_scenario.messages.len(), 2);
+        assert_eq!(active_scenario.winner, Some(1));
+    }
+}
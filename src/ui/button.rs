@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::{actions, tick};
+use crate::{actions, controls, replay, tick};
 
 const BUTTON_BORDER: Color = Color::hsl(0.0, 0.0, 0.5);
 const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
@@ -14,6 +14,8 @@ pub fn button_system(
     >,
     actions_query: Query<&actions::Action>,
     mut actions_writer: EventWriter<tick::ActionEvent>,
+    replay: Option<Res<replay::Replay>>,
+    selector_state: Option<Res<controls::SelectorState>>,
 ) {
     for (button_entity, interaction, mut color, mut border_color) in &mut interaction_query {
         match *interaction {
@@ -21,9 +23,14 @@ pub fn button_system(
                 *color = PRESSED_BUTTON.into();
                 border_color.0 = Color::RED;
 
-                if let Ok(action) = actions_query.get(button_entity) {
+                // Only the recorded actions may change a replayed game
+                if let (Ok(action), None, Some(selector_state)) =
+                    (actions_query.get(button_entity), &replay, &selector_state)
+                {
                     actions_writer.send(tick::ActionEvent {
                         action: action.clone(),
+                        empire: selector_state.local_empire,
+                        source: tick::ActionSource::Local,
                     });
                 }
             }
@@ -40,23 +47,24 @@ pub fn button_system(
 }
 
 pub fn make_button(action: &actions::Action) -> (ButtonBundle, actions::Action) {
-    (
-        ButtonBundle {
-            style: Style {
-                height: Val::Px(30.0),
-                border: UiRect::all(Val::Px(1.0)),
-                // horizontally center child text
-                justify_content: JustifyContent::Center,
-                // vertically center child text
-                align_items: AlignItems::Center,
-                ..default()
-            },
-            border_color: BorderColor(BUTTON_BORDER),
-            background_color: NORMAL_BUTTON.into(),
+    (make_menu_button(), action.clone())
+}
+
+pub fn make_menu_button() -> ButtonBundle {
+    ButtonBundle {
+        style: Style {
+            height: Val::Px(30.0),
+            border: UiRect::all(Val::Px(1.0)),
+            // horizontally center child text
+            justify_content: JustifyContent::Center,
+            // vertically center child text
+            align_items: AlignItems::Center,
             ..default()
         },
-        action.clone(),
-    )
+        border_color: BorderColor(BUTTON_BORDER),
+        background_color: NORMAL_BUTTON.into(),
+        ..default()
+    }
 }
 
 pub fn make_button_text(text: String) -> TextBundle {
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+
+use crate::config::CONFIG;
+use crate::{editor, tile};
+
+#[derive(Component)]
+pub struct EditorPanel;
+
+#[derive(Component)]
+pub struct EditorStatus;
+
+pub fn init(mut commands: Commands) {
+    commands
+        .spawn((
+            EditorPanel,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(30.0),
+                    justify_content: JustifyContent::SpaceBetween,
+                    align_items: AlignItems::Center,
+                    top: Val::Percent(6.),
+                    right: Val::Percent(25.),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                background_color: Color::rgb(0.1, 0.1, 0.1).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                EditorStatus,
+                TextBundle::from_section(
+                    "Editor",
+                    TextStyle {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(5.)),
+                    ..default()
+                }),
+                Label,
+            ));
+        });
+}
+
+pub fn despawn(mut commands: Commands, query: Query<Entity, With<EditorPanel>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn update(editor: Res<editor::Editor>, mut status_query: Query<&mut Text, With<EditorStatus>>) {
+    for mut text in status_query.iter_mut() {
+        text.sections[0].value = format!(
+            "Editor - brush: {:?} ({:?}), terrain: {} ({:?}), empire: {} ({:?})\nSave: {:?}, back to the game: {:?}",
+            editor.brush,
+            CONFIG.keys.editor_brush,
+            tile::tile_string(&editor.kind),
+            CONFIG.keys.editor_kind,
+            editor.empire,
+            CONFIG.keys.editor_empire,
+            CONFIG.keys.save_scenario,
+            CONFIG.keys.editor,
+        );
+    }
+}
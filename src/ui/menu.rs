@@ -0,0 +1,317 @@
+use bevy::prelude::*;
+
+use super::button;
+use crate::{config, empire, hotseat, net, replay, scenario, state::AppState, utils};
+
+#[derive(Component, Clone, Copy)]
+pub enum MenuButton {
+    NewGame,
+    HostGame,
+    JoinGame,
+    /// Starts the game saved to `scenario::SCENARIO_PATH` by the editor
+    Load,
+    /// Lists the recorded games
+    Replay,
+    /// Plays back the recorded game at this index of `replay::list_replays`
+    LoadReplay(usize),
+    Settings,
+    Quit,
+    Resume,
+    MainMenu,
+    Back,
+    CycleEmpires,
+    CycleHumanPlayers,
+    CycleWorldSize,
+    CycleMapType,
+    /// Switches between a random setup and `scenario::SCENARIO_PATH`
+    ToggleScenario,
+    Ready,
+}
+
+/// Marker for the root node of whichever menu screen is currently shown
+#[derive(Component)]
+pub struct MenuRoot;
+
+const EMPIRE_COUNTS: [i32; 5] = [2, 4, 6, 8, 10];
+const HUMAN_PLAYER_COUNTS: [i32; 4] = [1, 2, 3, 4];
+const MENU_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const WORLD_SIZES: [(i32, i32); 3] = [(50, 50), (100, 100), (200, 200)];
+/// Only the newest replays are offered
+const LISTED_REPLAYS: usize = 8;
+
+fn spawn_menu(
+    commands: &mut Commands,
+    title: &str,
+    background: Color,
+    buttons: Vec<(MenuButton, String)>,
+) {
+    commands
+        .spawn((
+            MenuRoot,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                background_color: background.into(),
+                z_index: ZIndex::Global(i32::MAX - 1),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(300.0),
+                        padding: UiRect::all(Val::Px(10.)),
+                        row_gap: Val::Px(10.),
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.1, 0.1, 0.1).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_section(
+                            title,
+                            TextStyle {
+                                font_size: 40.0,
+                                ..default()
+                            },
+                        )
+                        .with_style(Style {
+                            align_self: AlignSelf::Center,
+                            ..default()
+                        }),
+                    );
+
+                    for (menu_button, text) in buttons {
+                        parent
+                            .spawn((button::make_menu_button(), menu_button))
+                            .with_children(|parent| {
+                                parent.spawn(button::make_button_text(text));
+                            });
+                    }
+                });
+        });
+}
+
+fn spawn_main_menu(commands: &mut Commands) {
+    spawn_menu(
+        commands,
+        "Unciv",
+        MENU_BACKGROUND,
+        vec![
+            (MenuButton::NewGame, "New Game".to_string()),
+            (MenuButton::HostGame, "Host Network Game".to_string()),
+            (MenuButton::JoinGame, "Join Network Game".to_string()),
+            (MenuButton::Load, "Load Scenario".to_string()),
+            (MenuButton::Replay, "Replays".to_string()),
+            (MenuButton::Settings, "Settings".to_string()),
+            (MenuButton::Quit, "Quit".to_string()),
+        ],
+    );
+}
+
+fn spawn_settings(commands: &mut Commands, settings: &config::GameSettings) {
+    let (x, y) = settings.world_size;
+
+    spawn_menu(
+        commands,
+        "Settings",
+        MENU_BACKGROUND,
+        vec![
+            (
+                MenuButton::CycleEmpires,
+                format!("Empires: {}", settings.number_of_empires),
+            ),
+            (
+                MenuButton::CycleHumanPlayers,
+                format!("Hotseat players: {}", settings.human_players),
+            ),
+            (MenuButton::CycleWorldSize, format!("Map: {}x{}", x, y)),
+            (
+                MenuButton::CycleMapType,
+                format!("Map type: {:?}", settings.map_type),
+            ),
+            (
+                MenuButton::ToggleScenario,
+                format!(
+                    "Scenario: {}",
+                    settings.scenario.as_deref().unwrap_or("None")
+                ),
+            ),
+            (MenuButton::Back, "Back".to_string()),
+        ],
+    );
+}
+
+fn spawn_replays(commands: &mut Commands) {
+    let mut buttons: Vec<(MenuButton, String)> = replay::list_replays()
+        .iter()
+        .take(LISTED_REPLAYS)
+        .enumerate()
+        .map(|(index, path)| {
+            let name = std::path::Path::new(path)
+                .file_stem()
+                .map_or(path.clone(), |stem| stem.to_string_lossy().to_string());
+            (MenuButton::LoadReplay(index), name)
+        })
+        .collect();
+    buttons.push((MenuButton::Back, "Back".to_string()));
+
+    spawn_menu(commands, "Replays", MENU_BACKGROUND, buttons);
+}
+
+pub fn init_main_menu(mut commands: Commands) {
+    spawn_main_menu(&mut commands);
+}
+
+pub fn init_pause_menu(mut commands: Commands) {
+    spawn_menu(
+        &mut commands,
+        "Paused",
+        MENU_BACKGROUND,
+        vec![
+            (MenuButton::Resume, "Resume".to_string()),
+            (MenuButton::MainMenu, "Main Menu".to_string()),
+            (MenuButton::Quit, "Quit".to_string()),
+        ],
+    );
+}
+
+/// Names the winner of a scenario, if there is one
+pub fn init_game_over(
+    mut commands: Commands,
+    active_scenario: Option<Res<scenario::ActiveScenario>>,
+    empire_query: Query<&empire::Empire>,
+) {
+    let winner = active_scenario
+        .and_then(|active_scenario| active_scenario.winner)
+        .and_then(|winner| empire_query.iter().find(|empire| empire.id == winner));
+    let title = match winner {
+        Some(empire) => format!("Game Over - {} won", empire.name),
+        None => "Game Over".to_string(),
+    };
+
+    spawn_menu(
+        &mut commands,
+        &title,
+        MENU_BACKGROUND,
+        vec![
+            (MenuButton::NewGame, "New Game".to_string()),
+            (MenuButton::MainMenu, "Main Menu".to_string()),
+            (MenuButton::Quit, "Quit".to_string()),
+        ],
+    );
+}
+
+pub fn init_lobby(mut commands: Commands, settings: Res<config::GameSettings>) {
+    spawn_menu(
+        &mut commands,
+        &format!("Waiting for players on {}", settings.address),
+        MENU_BACKGROUND,
+        vec![(MenuButton::MainMenu, "Cancel".to_string())],
+    );
+}
+
+/// Fully opaque so the next player can't see the previous player's map
+pub fn init_pass_turn(mut commands: Commands, turn_order: Res<hotseat::TurnOrder>) {
+    spawn_menu(
+        &mut commands,
+        &format!("Player {}'s turn", turn_order.current_player() + 1),
+        Color::BLACK,
+        vec![(MenuButton::Ready, "Ready".to_string())],
+    );
+}
+
+pub fn despawn_menu(mut commands: Commands, query: Query<Entity, With<MenuRoot>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn menu_button_system(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    menu_query: Query<Entity, With<MenuRoot>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut settings: ResMut<config::GameSettings>,
+    mut app_exit_events: EventWriter<bevy::app::AppExit>,
+) {
+    for (interaction, menu_button) in interaction_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match menu_button {
+            MenuButton::NewGame => next_state.set(AppState::Loading),
+            MenuButton::HostGame => net::host(&mut commands, &settings, &mut next_state),
+            MenuButton::JoinGame => net::join(&mut commands, &settings, &mut next_state),
+            MenuButton::Load => scenario::start(&mut settings, &mut next_state),
+            MenuButton::Replay => {
+                for entity in menu_query.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                spawn_replays(&mut commands);
+            }
+            MenuButton::LoadReplay(index) => match replay::list_replays().get(*index) {
+                Some(path) => replay::load(path, &mut commands, &mut settings, &mut next_state),
+                None => println!("Replay {} is gone", index),
+            },
+            MenuButton::Settings
+            | MenuButton::CycleEmpires
+            | MenuButton::CycleHumanPlayers
+            | MenuButton::CycleWorldSize
+            | MenuButton::CycleMapType
+            | MenuButton::ToggleScenario => {
+                match menu_button {
+                    MenuButton::CycleEmpires => {
+                        settings.number_of_empires =
+                            utils::next_in_cycle(&EMPIRE_COUNTS, settings.number_of_empires);
+                    }
+                    MenuButton::CycleHumanPlayers => {
+                        settings.human_players =
+                            utils::next_in_cycle(&HUMAN_PLAYER_COUNTS, settings.human_players);
+                    }
+                    MenuButton::CycleWorldSize => {
+                        settings.world_size =
+                            utils::next_in_cycle(&WORLD_SIZES, settings.world_size);
+                    }
+                    MenuButton::CycleMapType => {
+                        settings.map_type =
+                            utils::next_in_cycle(&config::MAP_TYPES, settings.map_type);
+                    }
+                    MenuButton::ToggleScenario => {
+                        settings.scenario = match settings.scenario {
+                            Some(_) => None,
+                            None => Some(scenario::SCENARIO_PATH.to_string()),
+                        };
+                    }
+                    _ => (),
+                }
+
+                for entity in menu_query.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                spawn_settings(&mut commands, &settings);
+            }
+            MenuButton::Back => {
+                for entity in menu_query.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                spawn_main_menu(&mut commands);
+            }
+            MenuButton::Quit => {
+                app_exit_events.send(bevy::app::AppExit);
+            }
+            MenuButton::Resume | MenuButton::Ready => next_state.set(AppState::InGame),
+            MenuButton::MainMenu => next_state.set(AppState::MainMenu),
+        }
+    }
+}
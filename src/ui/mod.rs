@@ -1,4 +1,8 @@
 pub mod button;
+pub mod editor_panel;
 pub mod fps;
+pub mod menu;
 pub mod panels;
+pub mod replay_panel;
+pub mod scenario_panel;
 pub mod tick_panel;
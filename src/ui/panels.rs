@@ -1,21 +1,27 @@
 use bevy::prelude::*;
 
 use super::button;
-use crate::{actions, building, controls, empire, resource, tile, unit};
+use crate::{actions, building, controls, empire, resource, state, tile, unit, utils, world_gen};
 #[derive(Component)]
 pub struct ResourceUi;
 
+#[derive(Component)]
+pub struct UnitPanel;
+
 pub fn init(mut commands: Commands) {
     commands
-        .spawn(NodeBundle {
-            style: Style {
-                width: Val::Percent(20.0),
-                height: Val::Percent(100.0),
-                justify_content: JustifyContent::SpaceBetween,
+        .spawn((
+            state::GameEntity,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(20.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::SpaceBetween,
+                    ..default()
+                },
                 ..default()
             },
-            ..default()
-        })
+        ))
         .with_children(|parent| {
             parent
                 .spawn(NodeBundle {
@@ -23,7 +29,7 @@ pub fn init(mut commands: Commands) {
                         width: Val::Percent(100.0),
                         border: UiRect::all(Val::Px(2.)),
                         display: Display::Grid,
-                        grid_template_rows: RepeatedGridTrack::flex(2, 1.0),
+                        grid_template_rows: RepeatedGridTrack::flex(3, 1.0),
                         ..default()
                     },
                     background_color: Color::rgb(0.1, 0.1, 0.1).into(),
@@ -58,11 +64,42 @@ pub fn init(mut commands: Commands) {
                             ));
                         });
 
+                    init_unit_panel(parent);
                     init_entity_spawner_tab(parent);
                 });
         });
 }
 
+fn init_unit_panel(parent: &mut ChildBuilder) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                margin: UiRect::all(Val::Px(10.)),
+                display: Display::Grid,
+                ..default()
+            },
+            background_color: Color::rgb(0.15, 0.15, 0.15).into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                UnitPanel,
+                TextBundle::from_section(
+                    "No unit selected".to_string(),
+                    TextStyle {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(5.)),
+                    ..default()
+                }),
+                Label,
+            ));
+        });
+}
+
 fn init_entity_spawner_tab(parent: &mut ChildBuilder) {
     parent
         .spawn(NodeBundle {
@@ -80,13 +117,26 @@ fn init_entity_spawner_tab(parent: &mut ChildBuilder) {
 
             let buy_settler = actions::Action::BuyUnit(actions::BuyUnit {
                 unit_kind: unit::UnitKind::Settler(default()),
+                location: None,
             });
+            let upgrade = actions::Action::Upgrade(actions::Upgrade { location: None });
+            let undo = actions::Action::Undo;
 
             parent
                 .spawn(button::make_button(&buy_settler))
                 .with_children(|parent| {
                     parent.spawn(button::make_button_text("Settler".to_string()));
                 });
+            parent
+                .spawn(button::make_button(&upgrade))
+                .with_children(|parent| {
+                    parent.spawn(button::make_button_text("Upgrade".to_string()));
+                });
+            parent
+                .spawn(button::make_button(&undo))
+                .with_children(|parent| {
+                    parent.spawn(button::make_button_text("Undo".to_string()));
+                });
             parent
                 .spawn(button::make_button(&noop))
                 .with_children(|parent| {
@@ -111,18 +161,21 @@ pub struct EmpireName;
 
 pub fn init_tile_inspector(mut commands: Commands) {
     commands
-        .spawn(NodeBundle {
-            style: Style {
-                width: Val::Percent(80.0),
-                height: Val::Percent(20.0),
-                justify_content: JustifyContent::SpaceBetween,
-                right: Val::Px(0.),
-                bottom: Val::Px(0.),
-                position_type: PositionType::Absolute,
+        .spawn((
+            state::GameEntity,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(80.0),
+                    height: Val::Percent(20.0),
+                    justify_content: JustifyContent::SpaceBetween,
+                    right: Val::Px(0.),
+                    bottom: Val::Px(0.),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
                 ..default()
             },
-            ..default()
-        })
+        ))
         .with_children(|parent| {
             parent
                 .spawn(NodeBundle {
@@ -201,9 +254,10 @@ fn init_builder_tab(root: &mut ChildBuilder) {
 
 fn tile_to_string(tile: &tile::TileComponent) -> String {
     let kind = tile::tile_string(&tile.tile.kind);
+    let river = if tile.tile.river { " with river" } else { "" };
     format!(
-        "{}: ({}, {})",
-        kind, tile.tile.location.x, tile.tile.location.y
+        "{}{}: ({}, {})",
+        kind, river, tile.tile.location.x, tile.tile.location.y
     )
 }
 
@@ -218,6 +272,12 @@ pub fn update_tile_inspector(
         (With<TileInspectorBuildingList>, Without<TileInspectorTitle>),
     >,
     tile_query: Query<(Entity, &tile::TileComponent)>,
+    (world_state, tiles, unit_query, empire_query): (
+        Res<world_gen::WorldState>,
+        Query<&tile::TileComponent>,
+        Query<&unit::Unit>,
+        Query<&empire::Empire>,
+    ),
 ) {
     match ui_state.selected_tile {
         Some(entity) => {
@@ -233,7 +293,29 @@ pub fn update_tile_inspector(
                 .map(|building| building::building_name(building))
                 .collect();
 
-            let building_list = building_list.join("\t");
+            let mut building_list = building_list.join("\t");
+
+            let inventory = world_state
+                .empires
+                .get(&ui_state.local_empire)
+                .and_then(|empire_entity| empire_query.get(*empire_entity).ok())
+                .map(|empire| &empire.inventory);
+            if let Some(inventory) = inventory {
+                let options = actions::legal::enumerate(
+                    ui_state.local_empire,
+                    &world_state,
+                    &tiles,
+                    &unit_query,
+                    inventory,
+                );
+                for option in options
+                    .iter()
+                    .filter(|option| action_location(&option.action) == Some(tile.tile.location))
+                {
+                    building_list.push('\n');
+                    building_list.push_str(&option_to_string(option));
+                }
+            }
 
             set_query_text(&mut building_list_query, &building_list);
         }
@@ -244,6 +326,34 @@ pub fn update_tile_inspector(
     }
 }
 
+/// The tile an action is about, moves and founding cities are left out as
+/// they belong to the unit
+fn action_location(action: &actions::Action) -> Option<utils::Coordinates> {
+    match action {
+        actions::Action::BuyUnit(buy) => buy.location,
+        actions::Action::Upgrade(upgrade) => upgrade.location,
+        _ => None,
+    }
+}
+
+fn option_to_string(option: &actions::legal::ActionOption) -> String {
+    let mut text = actions::legal::describe(&option.action);
+
+    if !option.cost.is_empty() {
+        let cost: Vec<String> = option
+            .cost
+            .iter()
+            .map(|(resource, amount)| format!("{:?} {}", resource, amount))
+            .collect();
+        text += &format!(" ({})", cost.join(", "));
+    }
+    if let Some(reason) = &option.illegal_reason {
+        text += &format!(": {}", reason);
+    }
+
+    text
+}
+
 fn set_query_text<T: bevy::ecs::query::QueryFilter>(
     query: &mut Query<&mut Text, T>,
     new_text: &str,
@@ -253,6 +363,45 @@ fn set_query_text<T: bevy::ecs::query::QueryFilter>(
     }
 }
 
+pub fn update_unit_panel(
+    ui_state: Res<controls::SelectorState>,
+    mut unit_panel_query: Query<&mut Text, With<UnitPanel>>,
+    unit_query: Query<&unit::Unit>,
+) {
+    let text = match ui_state
+        .selected_unit
+        .and_then(|unit_entity| unit_query.get(unit_entity).ok())
+    {
+        Some(unit) => unit_to_string(unit),
+        None => "No unit selected".to_string(),
+    };
+
+    set_query_text(&mut unit_panel_query, &text);
+}
+
+fn unit_to_string(unit: &unit::Unit) -> String {
+    let kind = match unit.kind {
+        unit::UnitKind::Settler(_) => "Settler",
+        unit::UnitKind::Caravan(_) => "Caravan",
+    };
+    let mut text = format!(
+        "{} {}: ({}, {})",
+        kind, unit.id.0, unit.location.x, unit.location.y
+    );
+
+    if let Some(target) = unit.target {
+        text += &format!("\nTarget: ({}, {})", target.x, target.y);
+    }
+    if unit.orders.is_empty() {
+        text += "\nNo orders";
+    }
+    for (index, order) in unit.orders.iter().enumerate() {
+        text += &format!("\n{}. {}", index + 1, unit::orders::describe(order));
+    }
+
+    text
+}
+
 pub fn update_empire_panel(
     ui_state: ResMut<controls::SelectorState>,
     mut resources_inspector_query: Query<&mut Text, With<ResourceUi>>,
@@ -264,8 +413,8 @@ pub fn update_empire_panel(
 
             for mut text in resources_inspector_query.iter_mut() {
                 text.sections[0].value = format!(
-                    "Empire: {}\nWood: {}\nStone: {}",
-                    empire.id,
+                    "Empire: {}\nWood: {}\nStone: {}\nFood: {}",
+                    empire.name,
                     empire
                         .inventory
                         .items
@@ -276,6 +425,11 @@ pub fn update_empire_panel(
                         .items
                         .get(&resource::Resource::Stone)
                         .unwrap_or(&0),
+                    empire
+                        .inventory
+                        .items
+                        .get(&resource::Resource::Food)
+                        .unwrap_or(&0),
                 );
             }
         }
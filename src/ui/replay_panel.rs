@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+
+use super::button;
+use crate::{replay, state, world_gen};
+
+#[derive(Component, Clone, Copy)]
+pub enum ReplayControl {
+    TogglePlay,
+    Step,
+}
+
+#[derive(Component)]
+pub struct ReplayStatus;
+
+pub fn init(mut commands: Commands) {
+    commands
+        .spawn((
+            state::GameEntity,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(30.0),
+                    height: Val::Percent(5.0),
+                    justify_content: JustifyContent::SpaceBetween,
+                    align_items: AlignItems::Center,
+                    top: Val::Percent(6.),
+                    right: Val::Percent(25.),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                background_color: Color::rgb(0.1, 0.1, 0.1).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                ReplayStatus,
+                TextBundle::from_section(
+                    "Replay",
+                    TextStyle {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(5.)),
+                    ..default()
+                }),
+                Label,
+            ));
+            parent
+                .spawn((button::make_menu_button(), ReplayControl::TogglePlay))
+                .with_children(|parent| {
+                    parent.spawn(button::make_button_text("Play/Pause".to_string()));
+                });
+            parent
+                .spawn((button::make_menu_button(), ReplayControl::Step))
+                .with_children(|parent| {
+                    parent.spawn(button::make_button_text("Step".to_string()));
+                });
+        });
+}
+
+pub fn replay_button_system(
+    interaction_query: Query<(&Interaction, &ReplayControl), Changed<Interaction>>,
+    mut replay: ResMut<replay::Replay>,
+) {
+    for (interaction, control) in interaction_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match control {
+            ReplayControl::TogglePlay => replay.playing = !replay.playing,
+            ReplayControl::Step => replay.step_requested = true,
+        }
+    }
+}
+
+pub fn update(
+    replay: Res<replay::Replay>,
+    world_state: Res<world_gen::WorldState>,
+    mut status_query: Query<&mut Text, With<ReplayStatus>>,
+) {
+    let status = if replay.finished() {
+        "finished"
+    } else if replay.playing {
+        "playing"
+    } else {
+        "paused"
+    };
+
+    for mut text in status_query.iter_mut() {
+        text.sections[0].value = format!(
+            "Turn {}/{} ({})",
+            world_state.turn,
+            replay.last_turn() + 1,
+            status
+        );
+    }
+}
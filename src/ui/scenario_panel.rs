@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+
+use crate::{scenario, state};
+
+/// How many of the latest scenario messages are shown
+const SHOWN_MESSAGES: usize = 3;
+
+#[derive(Component)]
+pub struct ScenarioMessages;
+
+pub fn init(mut commands: Commands) {
+    commands
+        .spawn((
+            state::GameEntity,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(30.0),
+                    top: Val::Percent(6.),
+                    right: Val::Percent(25.),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                ScenarioMessages,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(5.)),
+                    ..default()
+                }),
+                Label,
+            ));
+        });
+}
+
+pub fn update(
+    active_scenario: Res<scenario::ActiveScenario>,
+    mut text_query: Query<&mut Text, With<ScenarioMessages>>,
+) {
+    if !active_scenario.is_changed() {
+        return;
+    }
+
+    let first = active_scenario
+        .messages
+        .len()
+        .saturating_sub(SHOWN_MESSAGES);
+    let messages = active_scenario.messages[first..].join("\n");
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = messages.clone();
+    }
+}
@@ -1,24 +1,27 @@
 use bevy::prelude::*;
 
 use super::button;
-use crate::actions;
+use crate::{actions, state};
 
 pub fn init(mut commands: Commands) {
     commands
-        .spawn(NodeBundle {
-            style: Style {
-                width: Val::Percent(20.0),
-                height: Val::Percent(5.0),
-                justify_content: JustifyContent::SpaceBetween,
-                top: Val::Px(0.),
-                right: Val::Percent(30.),
-                position_type: PositionType::Absolute,
-                align_content: AlignContent::Center,
+        .spawn((
+            state::GameEntity,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(20.0),
+                    height: Val::Percent(5.0),
+                    justify_content: JustifyContent::SpaceBetween,
+                    top: Val::Px(0.),
+                    right: Val::Percent(30.),
+                    position_type: PositionType::Absolute,
+                    align_content: AlignContent::Center,
+                    ..default()
+                },
+                background_color: Color::rgb(0.1, 0.1, 0.1).into(),
                 ..default()
             },
-            background_color: Color::rgb(0.1, 0.1, 0.1).into(),
-            ..default()
-        })
+        ))
         .with_children(|parent| {
             parent
                 .spawn(button::make_button(&actions::Action::EndTurn))
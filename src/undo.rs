@@ -0,0 +1,342 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    actions, animation, building, controls, empire, resource, tile, unit, utils, world_gen,
+};
+
+/// What an action is about to change, taken right before it is executed
+pub struct UndoEntry {
+    empire: i32,
+    tiles: Vec<tile::TileComponent>,
+    /// `None` for units the action spawns
+    units: Vec<(unit::UnitId, Option<unit::Unit>)>,
+    inventory: HashMap<resource::Resource, i32>,
+    next_unit_id: unit::UnitId,
+}
+
+/// The actions executed during the current turn, newest last
+#[derive(Resource, Default)]
+pub struct UndoStack {
+    entries: Vec<UndoEntry>,
+}
+
+impl UndoStack {
+    pub fn push(&mut self, entry: UndoEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Adds what `entry` saved to the newest entry, so actions sent together
+    /// are undone together, the state saved first is kept
+    pub fn merge(&mut self, entry: UndoEntry) {
+        let Some(last) = self.entries.last_mut() else {
+            return self.push(entry);
+        };
+        if last.empire != entry.empire {
+            return self.push(entry);
+        }
+
+        for tile in entry.tiles {
+            if !last
+                .tiles
+                .iter()
+                .any(|saved| saved.tile.location == tile.tile.location)
+            {
+                last.tiles.push(tile);
+            }
+        }
+        for (unit_id, unit) in entry.units {
+            if !last.units.iter().any(|(saved_id, _)| *saved_id == unit_id) {
+                last.units.push((unit_id, unit));
+            }
+        }
+    }
+
+    /// The newest entry, if it was done by `empire`
+    pub fn pop(&mut self, empire: i32) -> Option<UndoEntry> {
+        match self.entries.last() {
+            Some(entry) if entry.empire == empire => self.entries.pop(),
+            _ => None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Drops the entries of a player who ended their turn
+    pub fn clear_empire(&mut self, empire: i32) {
+        self.entries.retain(|entry| entry.empire != empire);
+    }
+}
+
+pub fn init(mut commands: Commands) {
+    commands.insert_resource(UndoStack::default());
+}
+
+/// Records the state `action` will change, `None` if there is nothing to undo
+/// or the action will be rejected
+pub fn snapshot(
+    action: &actions::Action,
+    acting_empire: i32,
+    world_state: &world_gen::WorldState,
+    tile_query: &Query<&mut tile::TileComponent>,
+    unit_query: &Query<(Entity, &Transform, &mut unit::Unit)>,
+    empire_query: &Query<&mut empire::Empire>,
+) -> Option<UndoEntry> {
+    actions::validate(
+        action,
+        acting_empire,
+        tile_query,
+        unit_query,
+        empire_query,
+        world_state,
+    )
+    .ok()?;
+
+    let (locations, unit_ids) = match action {
+        actions::Action::FoundCity(unit_id) => {
+            let (_, _, unit) = unit_query.get(*world_state.units.get(unit_id)?).ok()?;
+            (vec![unit.location], vec![*unit_id])
+        }
+        actions::Action::Upgrade(actions::Upgrade {
+            location: Some(location),
+        }) => (vec![*location], vec![]),
+        actions::Action::BuyUnit(_) => (vec![], vec![world_state.peek_unit_id()]),
+        actions::Action::KillUnit(unit_id)
+        | actions::Action::MoveUnit(actions::MoveUnit { unit: unit_id, .. })
+        | actions::Action::SetTarget(actions::SetTarget { unit: unit_id, .. })
+        | actions::Action::SetOrders(actions::SetOrders { unit: unit_id, .. }) => {
+            (vec![], vec![*unit_id])
+        }
+        _ => return None,
+    };
+
+    let tiles = locations
+        .iter()
+        .filter_map(|location| world_state.tile_entities.get(location))
+        .filter_map(|tile_entity| tile_query.get(*tile_entity).ok())
+        .cloned()
+        .collect();
+    let units = unit_ids
+        .into_iter()
+        .map(|unit_id| {
+            let unit = world_state
+                .units
+                .get(&unit_id)
+                .and_then(|unit_entity| unit_query.get(*unit_entity).ok())
+                .map(|(_, _, unit)| unit.clone());
+            (unit_id, unit)
+        })
+        .collect();
+    let inventory = world_state
+        .empires
+        .get(&acting_empire)
+        .and_then(|empire_entity| empire_query.get(*empire_entity).ok())
+        .map(|empire| empire.inventory.items.clone())
+        .unwrap_or_default();
+
+    Some(UndoEntry {
+        empire: acting_empire,
+        tiles,
+        units,
+        inventory,
+        next_unit_id: world_state.peek_unit_id(),
+    })
+}
+
+/// Puts everything in the entry back, despawned units are spawned again and
+/// units the action bought are removed
+pub fn restore(
+    entry: UndoEntry,
+    commands: &mut Commands,
+    tile_query: &mut Query<&mut tile::TileComponent>,
+    unit_query: &mut Query<(Entity, &Transform, &mut unit::Unit)>,
+    empire_query: &mut Query<&mut empire::Empire>,
+    selector_state: &mut controls::SelectorState,
+    world_state: &mut world_gen::WorldState,
+    building_resources: &Res<building::BuildingResources>,
+    unit_resources: &Res<unit::UnitResources>,
+    time: &Time,
+) {
+    for saved_tile in entry.tiles {
+        let tile_entity = *world_state
+            .tile_entities
+            .get(&saved_tile.tile.location)
+            .unwrap();
+
+        // Buildings are the only children of tiles
+        commands.entity(tile_entity).despawn_descendants();
+        if let Some(building) = &saved_tile.building {
+            let building_bundle = building::make_bundle(building, building_resources);
+            let building_id = commands.spawn(building_bundle).id();
+            commands.entity(tile_entity).push_children(&[building_id]);
+        }
+
+        *tile_query.get_mut(tile_entity).unwrap() = saved_tile;
+    }
+
+    for (unit_id, saved_unit) in entry.units {
+        let unit_entity = world_state.units.get(&unit_id).copied();
+
+        match (unit_entity, saved_unit) {
+            (Some(unit_entity), Some(saved_unit)) => {
+                let (_, transform, mut unit) = unit_query.get_mut(unit_entity).unwrap();
+                world_state.occupancy.relocate(&unit, saved_unit.location);
+
+                let (x, y) = utils::to_world_location(&saved_unit.location);
+                let z = unit::unit_height(world_state, &saved_unit.location);
+                commands
+                    .entity(unit_entity)
+                    .insert(animation::TranslationAnimation {
+                        start: transform.translation,
+                        end: Vec3::new(x, y, z),
+                        start_time: time.elapsed_seconds(),
+                        duration: 0.5,
+                    });
+
+                *unit = saved_unit;
+            }
+            (Some(unit_entity), None) => {
+                if let Ok((_, _, unit)) = unit_query.get(unit_entity) {
+                    world_state.occupancy.remove(unit_id, &unit.location);
+                }
+                commands.entity(unit_entity).despawn();
+                world_state.units.remove(&unit_id);
+            }
+            (None, Some(saved_unit)) => {
+                world_state.occupancy.add(&saved_unit);
+                let unit_bundle =
+                    unit::make_bundle(saved_unit, unit_resources, &world_state.tile_data);
+                let unit_entity = commands.spawn(unit_bundle).id();
+                world_state.units.insert(unit_id, unit_entity);
+            }
+            (None, None) => (),
+        }
+    }
+
+    if let Some(empire_entity) = world_state.empires.get(&entry.empire) {
+        if let Ok(mut empire) = empire_query.get_mut(*empire_entity) {
+            empire.inventory.items = entry.inventory;
+        }
+    }
+
+    world_state.rewind_unit_id(entry.next_unit_id);
+    selector_state.selected_unit = None;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{checksum, config, headless, tick};
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn hash(app: &mut App) -> u64 {
+        app.world.run_system_once(
+            |world_state: Res<world_gen::WorldState>,
+             tile_query: Query<&tile::TileComponent>,
+             unit_query: Query<&unit::Unit>,
+             empire_query: Query<&empire::Empire>| {
+                checksum::state_hash(
+                    &world_state,
+                    tile_query.iter(),
+                    unit_query.iter(),
+                    empire_query.iter(),
+                )
+            },
+        )
+    }
+
+    /// Sends the actions in the same frame, like the UI does for a tile action
+    fn send_all(app: &mut App, actions: Vec<actions::Action>) {
+        for action in actions {
+            app.world.send_event(tick::ActionEvent {
+                action,
+                empire: 0,
+                source: tick::ActionSource::Local,
+            });
+        }
+        app.update();
+    }
+
+    fn new_game(human_players: i32) -> (App, unit::Unit) {
+        let mut app = headless::test_game(config::GameSettings {
+            human_players,
+            ..headless::test_settings(3)
+        });
+
+        let settler = app
+            .world
+            .query::<&unit::Unit>()
+            .iter(&app.world)
+            .find(|unit| unit.owner == Some(0))
+            .unwrap()
+            .clone();
+        (app, settler)
+    }
+
+    #[test]
+    fn undoing_every_action_restores_the_turn() {
+        let (mut app, settler) = new_game(1);
+        let before = hash(&mut app);
+
+        // The settler has to make room for the caravan
+        headless::send(&mut app, 0, actions::Action::KillUnit(settler.id));
+        headless::send(
+            &mut app,
+            0,
+            actions::Action::BuyUnit(actions::BuyUnit {
+                unit_kind: unit::UnitKind::Caravan(default()),
+                location: Some(settler.location),
+            }),
+        );
+        let units = app.world.query::<&unit::Unit>().iter(&app.world).count();
+        assert_eq!(units, 2);
+        assert_ne!(hash(&mut app), before);
+
+        headless::send(&mut app, 0, actions::Action::Undo);
+        headless::send(&mut app, 0, actions::Action::Undo);
+        assert_eq!(hash(&mut app), before);
+
+        // Nothing is left to undo, and ending the turn clears the stack
+        headless::send(&mut app, 0, actions::Action::Undo);
+        assert_eq!(hash(&mut app), before);
+        headless::send(&mut app, 0, actions::Action::KillUnit(settler.id));
+        headless::send(&mut app, 0, actions::Action::EndTurn);
+        let after_turn = hash(&mut app);
+        headless::send(&mut app, 0, actions::Action::Undo);
+        assert_eq!(hash(&mut app), after_turn);
+    }
+
+    #[test]
+    fn actions_sent_together_are_undone_together() {
+        let (mut app, settler) = new_game(1);
+        let before = hash(&mut app);
+
+        send_all(
+            &mut app,
+            vec![
+                actions::Action::KillUnit(settler.id),
+                actions::Action::BuyUnit(actions::BuyUnit {
+                    unit_kind: unit::UnitKind::Caravan(default()),
+                    location: Some(settler.location),
+                }),
+            ],
+        );
+        assert_ne!(hash(&mut app), before);
+
+        headless::send(&mut app, 0, actions::Action::Undo);
+        assert_eq!(hash(&mut app), before);
+    }
+
+    #[test]
+    fn hotseat_players_cannot_undo_an_ended_turn() {
+        let (mut app, settler) = new_game(2);
+
+        headless::send(&mut app, 0, actions::Action::KillUnit(settler.id));
+        headless::send(&mut app, 0, actions::Action::EndTurn);
+        let after_turn = hash(&mut app);
+
+        headless::send(&mut app, 0, actions::Action::Undo);
+        assert_eq!(hash(&mut app), after_turn);
+    }
+}
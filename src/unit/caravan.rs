@@ -1,9 +1,10 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use super::UnitTrait;
-use crate::{actions, tile};
+use crate::{actions, tile, unit};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Caravan {}
 
 impl Default for Caravan {
@@ -30,13 +31,7 @@ pub fn init_resources<'a, 'b>(
 }
 
 impl UnitTrait for Caravan {
-    fn tile_action(
-        &self,
-        _: Mut<tile::TileComponent>,
-        _: Entity,
-        _: Entity,
-        _: i32,
-    ) -> Vec<actions::Action> {
+    fn tile_action(&self, _: &tile::TileComponent, _: &unit::Unit, _: i32) -> Vec<actions::Action> {
         vec![]
     }
 }
@@ -1,5 +1,8 @@
+use std::collections::VecDeque;
+
 use bevy::{prelude::*, utils::HashMap};
 use bevy_mod_picking::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     actions, animation, colors, controls, empire,
@@ -8,18 +11,31 @@ use crate::{
 };
 
 pub mod caravan;
+pub mod occupancy;
+pub mod orders;
 pub mod settler;
 
-#[derive(Component, Clone, Debug)]
+/// Stable identifier of a unit, unlike `Entity` it is the same across runs of
+/// the same game so it can be stored in logged actions
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub struct UnitId(pub u32);
+
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
 pub struct Unit {
+    pub id: UnitId,
     pub kind: UnitKind,
     pub location: utils::Coordinates,
     pub target: Option<utils::Coordinates>,
     pub owner: Option<i32>,
     pub moved: bool,
+    /// Set when the unit crossed a river, it can't move in the next turn
+    #[serde(default)]
+    pub skip_next_move: bool,
+    /// Worked through front to back at the end of every turn
+    pub orders: Vec<orders::Order>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum UnitKind {
     Settler(settler::Settler),
     Caravan(caravan::Caravan),
@@ -28,11 +44,14 @@ pub enum UnitKind {
 impl Default for Unit {
     fn default() -> Self {
         Unit {
+            id: UnitId::default(),
             kind: UnitKind::Settler(settler::Settler::default()),
             location: utils::Coordinates { x: 0, y: 0 },
             target: None,
             owner: None,
             moved: false,
+            skip_next_move: false,
+            orders: Vec::new(),
         }
     }
 }
@@ -118,9 +137,8 @@ pub fn make_bundle(
 trait UnitTrait {
     fn tile_action(
         &self,
-        tile: Mut<tile::TileComponent>,
-        unit_entity: Entity,
-        tile_entity: Entity,
+        tile: &tile::TileComponent,
+        unit: &Unit,
         acting_empire: i32,
     ) -> Vec<actions::Action>;
 }
@@ -153,41 +171,96 @@ pub fn get_normal_material(
 
 pub fn tile_action(
     unit: &Unit,
-    tile: Mut<tile::TileComponent>,
-    unit_entity: Entity,
-    tile_entity: Entity,
+    tile: &tile::TileComponent,
     acting_empire: i32,
 ) -> Vec<actions::Action> {
     match &unit.kind {
-        UnitKind::Settler(settler) => {
-            settler.tile_action(tile, unit_entity, tile_entity, acting_empire)
-        }
-        UnitKind::Caravan(caravan) => {
-            caravan.tile_action(tile, unit_entity, tile_entity, acting_empire)
-        }
+        UnitKind::Settler(settler) => settler.tile_action(tile, unit, acting_empire),
+        UnitKind::Caravan(caravan) => caravan.tile_action(tile, unit, acting_empire),
     }
 }
 
-pub fn unit_height(
-    world_state: &Res<world_gen::WorldState>,
-    coordinates: &utils::Coordinates,
-) -> f32 {
+pub fn unit_height(world_state: &world_gen::WorldState, coordinates: &utils::Coordinates) -> f32 {
     return world_state.tile_data.get(coordinates).unwrap().height + TILE_SIZE as f32 / 2.;
 }
 
-// TODO: Do not walk on water
-pub fn next_location(unit: &Unit, _world_state: &Res<world_gen::WorldState>) -> utils::Coordinates {
-    match (unit.target, unit.moved) {
-        (Some(target), false) => {
-            let delta = utils::Coordinates {
-                x: (target.x - unit.location.x).clamp(-1, 1),
-                y: (target.y - unit.location.y).clamp(-1, 1),
-            };
-            unit.location + delta
+/// The next step along the shortest path over land to the unit's target,
+/// units step around full tiles and wait when every closer tile is full or
+/// the target can't be reached
+pub fn next_location(unit: &Unit, world_state: &world_gen::WorldState) -> utils::Coordinates {
+    let (Some(target), false) = (unit.target, unit.moved) else {
+        return unit.location;
+    };
+    if target == unit.location {
+        return target;
+    }
+
+    let distances = distances_to(&target, &unit.location, &world_state.tile_data);
+    let Some(distance) = distances.get(&unit.location) else {
+        return unit.location;
+    };
+
+    // Any neighbour one step closer will do when the first one is full
+    utils::DIRECTIONS
+        .iter()
+        .map(|(x, y)| unit.location + utils::Coordinates { x: *x, y: *y })
+        .filter(|next| distances.get(next) == Some(&(distance - 1)))
+        .find(|next| world_state.occupancy.can_enter(next, &unit.kind).is_ok())
+        .unwrap_or(unit.location)
+}
+
+/// Steps over land from every tile to `target`, searched breadth first from
+/// the target until `start` is reached
+fn distances_to(
+    target: &utils::Coordinates,
+    start: &utils::Coordinates,
+    tile_data: &HashMap<utils::Coordinates, tile::Tile>,
+) -> HashMap<utils::Coordinates, i32> {
+    let is_land = |location: &utils::Coordinates| {
+        tile_data
+            .get(location)
+            .map_or(false, |tile| tile::is_land(&tile.kind))
+    };
+
+    let mut distances = HashMap::new();
+    if !is_land(target) {
+        return distances;
+    }
+    distances.insert(*target, 0);
+    let mut frontier = VecDeque::from([*target]);
+
+    while let Some(location) = frontier.pop_front() {
+        if location == *start {
+            break;
+        }
+        let distance = distances[&location];
+
+        for (x, y) in utils::DIRECTIONS.iter() {
+            let next = location + utils::Coordinates { x: *x, y: *y };
+            if is_land(&next) && !distances.contains_key(&next) {
+                distances.insert(next, distance + 1);
+                frontier.push_back(next);
+            }
         }
-        (_, true) => unit.location,
-        (None, _) => unit.location,
     }
+
+    distances
+}
+
+/// Units entering a river from its bank lose their next move
+pub fn crosses_river(
+    world_state: &world_gen::WorldState,
+    from: &utils::Coordinates,
+    to: &utils::Coordinates,
+) -> bool {
+    let river = |location| {
+        world_state
+            .tile_data
+            .get(location)
+            .map_or(false, |tile: &tile::Tile| tile.river)
+    };
+
+    from != to && river(to) && !river(from)
 }
 
 pub fn next_location_update<'a, 'b, 'c>(
@@ -196,12 +269,13 @@ pub fn next_location_update<'a, 'b, 'c>(
     time: &Res<Time>,
     transform: &Transform,
     unit_entity: &Entity,
-    world_state: &Res<world_gen::WorldState>,
+    world_state: &mut world_gen::WorldState,
     next_location: &utils::Coordinates,
 ) -> (Commands<'b, 'c>, Mut<'a, Unit>) {
     let (x, y) = utils::to_world_location(&next_location);
     let z = unit::unit_height(&world_state, &next_location);
 
+    world_state.occupancy.relocate(&unit, *next_location);
     unit.location = *next_location;
 
     commands
@@ -215,3 +289,152 @@ pub fn next_location_update<'a, 'b, 'c>(
 
     (commands, unit)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::headless;
+
+    /// `#` is land, `~` water, the first row is the northern one
+    fn world_from_rows(rows: &[&str]) -> world_gen::WorldState {
+        let mut world_state = world_gen::WorldState::default();
+        for (row, line) in rows.iter().enumerate() {
+            for (x, symbol) in line.chars().enumerate() {
+                let location = utils::Coordinates {
+                    x: x as i32,
+                    y: (rows.len() - 1 - row) as i32,
+                };
+                let kind = match symbol {
+                    '#' => tile::TileKind::Grassland,
+                    _ => tile::TileKind::Ocean,
+                };
+                world_state.tile_data.insert(
+                    location,
+                    tile::Tile {
+                        location,
+                        kind,
+                        height: 0.,
+                        river: false,
+                        body: 0,
+                    },
+                );
+            }
+        }
+        world_state
+    }
+
+    #[test]
+    fn units_walk_around_water() {
+        let world_state = world_from_rows(&["#####", "#~~~#", "#~~~#"]);
+        let mut unit = Unit {
+            location: utils::Coordinates { x: 0, y: 0 },
+            target: Some(utils::Coordinates { x: 4, y: 0 }),
+            ..default()
+        };
+
+        let mut path = vec![];
+        while unit.location != unit.target.unwrap() && path.len() < 10 {
+            unit.location = next_location(&unit, &world_state);
+            path.push((unit.location.x, unit.location.y));
+        }
+
+        assert_eq!(path, vec![(0, 1), (1, 2), (2, 2), (3, 2), (4, 1), (4, 0)]);
+    }
+
+    #[test]
+    fn units_crossing_paths_step_around_each_other() {
+        let mut world_state = world_from_rows(&["#####", "#####", "#####"]);
+        let mut units = vec![
+            Unit {
+                id: UnitId(0),
+                location: utils::Coordinates { x: 0, y: 1 },
+                target: Some(utils::Coordinates { x: 4, y: 1 }),
+                ..default()
+            },
+            Unit {
+                id: UnitId(1),
+                location: utils::Coordinates { x: 4, y: 1 },
+                target: Some(utils::Coordinates { x: 0, y: 1 }),
+                ..default()
+            },
+        ];
+        for unit in units.iter() {
+            world_state.occupancy.add(unit);
+        }
+
+        for _ in 0..6 {
+            for unit in units.iter_mut() {
+                let next = next_location(unit, &world_state);
+                world_state.occupancy.relocate(unit, next);
+                unit.location = next;
+            }
+        }
+
+        for unit in units.iter() {
+            assert_eq!(Some(unit.location), unit.target);
+        }
+    }
+
+    #[test]
+    fn moving_onto_a_river_takes_the_next_move() {
+        let mut app = headless::test_game(headless::test_settings(7));
+
+        let settler = app
+            .world
+            .query::<&Unit>()
+            .iter(&app.world)
+            .find(|unit| unit.owner == Some(0))
+            .unwrap()
+            .clone();
+        let mut world_state = app.world.resource_mut::<world_gen::WorldState>();
+        let river = utils::DIRECTIONS
+            .iter()
+            .map(|(x, y)| settler.location + utils::Coordinates { x: *x, y: *y })
+            .find(|location| {
+                world_state
+                    .tile_data
+                    .get(location)
+                    .map_or(false, |tile| tile::is_land(&tile.kind))
+            })
+            .unwrap();
+        world_state
+            .tile_data
+            .get_mut(&settler.location)
+            .unwrap()
+            .river = false;
+        world_state.tile_data.get_mut(&river).unwrap().river = true;
+
+        headless::send(
+            &mut app,
+            0,
+            actions::Action::MoveUnit(actions::MoveUnit {
+                unit: settler.id,
+                target: river,
+            }),
+        );
+        headless::send(
+            &mut app,
+            0,
+            actions::Action::SetTarget(actions::SetTarget {
+                unit: settler.id,
+                target: Some(settler.location),
+            }),
+        );
+        headless::send(&mut app, 0, actions::Action::EndTurn);
+
+        // The turn after the crossing is lost, the one after that isn't
+        let location = |app: &mut App| {
+            app.world
+                .query::<&Unit>()
+                .iter(&app.world)
+                .find(|unit| unit.id == settler.id)
+                .unwrap()
+                .location
+        };
+        assert_eq!(location(&mut app), river);
+        headless::send(&mut app, 0, actions::Action::EndTurn);
+        assert_eq!(location(&mut app), river);
+        headless::send(&mut app, 0, actions::Action::EndTurn);
+        assert_eq!(location(&mut app), settler.location);
+    }
+}
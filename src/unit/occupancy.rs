@@ -0,0 +1,101 @@
+use bevy::utils::HashMap;
+
+use super::{Unit, UnitId, UnitKind};
+use crate::utils;
+
+pub const MAX_MILITARY_PER_TILE: usize = 1;
+pub const MAX_CIVILIANS_PER_TILE: usize = 1;
+
+/// The units standing on every tile, kept up to date whenever a unit is
+/// spawned, moved or removed
+#[derive(Default)]
+pub struct Occupancy {
+    tiles: HashMap<utils::Coordinates, Vec<(UnitId, bool)>>,
+}
+
+impl Occupancy {
+    pub fn units_at(&self, location: &utils::Coordinates) -> Vec<UnitId> {
+        self.tiles
+            .get(location)
+            .map(|units| units.iter().map(|(unit_id, _)| *unit_id).collect())
+            .unwrap_or_default()
+    }
+
+    /// Checks the stacking limit for a unit of `kind` entering the tile
+    pub fn can_enter(&self, location: &utils::Coordinates, kind: &UnitKind) -> Result<(), String> {
+        let military = is_military(kind);
+        let same_class = self.tiles.get(location).map_or(0, |units| {
+            units
+                .iter()
+                .filter(|(_, unit_military)| *unit_military == military)
+                .count()
+        });
+
+        let limit = match military {
+            true => MAX_MILITARY_PER_TILE,
+            false => MAX_CIVILIANS_PER_TILE,
+        };
+        if same_class >= limit {
+            return Err("Tile is full".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn add(&mut self, unit: &Unit) {
+        self.tiles
+            .entry(unit.location)
+            .or_default()
+            .push((unit.id, is_military(&unit.kind)));
+    }
+
+    pub fn remove(&mut self, unit_id: UnitId, location: &utils::Coordinates) {
+        if let Some(units) = self.tiles.get_mut(location) {
+            units.retain(|(id, _)| *id != unit_id);
+            if units.is_empty() {
+                self.tiles.remove(location);
+            }
+        }
+    }
+
+    /// Call before `unit.location` is changed to `to`
+    pub fn relocate(&mut self, unit: &Unit, to: utils::Coordinates) {
+        self.remove(unit.id, &unit.location);
+        self.add(&Unit {
+            location: to,
+            ..unit.clone()
+        });
+    }
+}
+
+pub fn is_military(kind: &UnitKind) -> bool {
+    match kind {
+        UnitKind::Settler(_) | UnitKind::Caravan(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn civilians_do_not_stack() {
+        let mut occupancy = Occupancy::default();
+        let settler = Unit {
+            id: UnitId(1),
+            ..Default::default()
+        };
+        let caravan = UnitKind::Caravan(Default::default());
+        let next_tile = utils::Coordinates { x: 1, y: 0 };
+
+        occupancy.add(&settler);
+        assert_eq!(occupancy.units_at(&settler.location), vec![UnitId(1)]);
+        assert!(occupancy.can_enter(&settler.location, &caravan).is_err());
+        assert!(occupancy.can_enter(&next_tile, &caravan).is_ok());
+
+        occupancy.relocate(&settler, next_tile);
+        assert!(occupancy.units_at(&settler.location).is_empty());
+        assert!(occupancy.can_enter(&settler.location, &caravan).is_ok());
+        assert!(occupancy.can_enter(&next_tile, &caravan).is_err());
+    }
+}
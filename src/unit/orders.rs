@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+
+use bevy::utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+use super::Unit;
+use crate::{tile, utils};
+
+/// Sentries wake up once a unit of another empire gets this close
+pub const SENTRY_RANGE: i32 = 2;
+
+/// A standing order, units work through their queue at the end of every turn
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Order {
+    MoveTo(utils::Coordinates),
+    /// Only settlers can found cities, the unit is used up
+    FoundCity,
+    /// Walks back and forth forever
+    Patrol(utils::Coordinates, utils::Coordinates),
+    /// Stays put until a unit of another empire comes close
+    Sentry,
+    /// Heads for the closest unexplored land every turn, until everything in
+    /// reach is explored or a unit of another empire comes close
+    Explore,
+}
+
+/// What the unit does this turn after its queue was looked at
+#[derive(PartialEq, Debug)]
+pub enum Plan {
+    /// Walk towards `Unit::target`, if there is one
+    Walk,
+    FoundCity,
+}
+
+/// Drops the finished orders at the front of the queue and points the unit
+/// at the next one
+pub fn plan(
+    unit: &mut Unit,
+    enemy_near: bool,
+    tile_data: &HashMap<utils::Coordinates, tile::Tile>,
+    explored: &HashSet<utils::Coordinates>,
+) -> Plan {
+    while let Some(order) = unit.orders.first().cloned() {
+        match order {
+            Order::MoveTo(target) => {
+                if unit.location != target {
+                    unit.target = Some(target);
+                    return Plan::Walk;
+                }
+                unit.orders.remove(0);
+            }
+            Order::FoundCity => {
+                unit.orders.remove(0);
+                return Plan::FoundCity;
+            }
+            Order::Patrol(start, end) => {
+                if unit.target.map_or(true, |target| target == unit.location) {
+                    unit.target = Some(if unit.location == start { end } else { start });
+                }
+                return Plan::Walk;
+            }
+            Order::Sentry => {
+                if !enemy_near {
+                    unit.target = None;
+                    return Plan::Walk;
+                }
+                println!("Unit {} spotted an enemy", unit.id.0);
+                unit.orders.remove(0);
+            }
+            Order::Explore => {
+                let target = match enemy_near {
+                    false => nearest_unexplored(unit.location, tile_data, explored),
+                    true => None,
+                };
+                if let Some(target) = target {
+                    unit.target = Some(target);
+                    return Plan::Walk;
+                }
+                println!("Unit {} stopped exploring", unit.id.0);
+                unit.target = None;
+                unit.orders.remove(0);
+            }
+        }
+    }
+
+    Plan::Walk
+}
+
+/// The closest unexplored land tile that can be reached over land, searched
+/// breadth first so ties always go the same way
+pub fn nearest_unexplored(
+    start: utils::Coordinates,
+    tile_data: &HashMap<utils::Coordinates, tile::Tile>,
+    explored: &HashSet<utils::Coordinates>,
+) -> Option<utils::Coordinates> {
+    let mut visited = HashSet::new();
+    let mut frontier = VecDeque::from([start]);
+    visited.insert(start);
+
+    while let Some(location) = frontier.pop_front() {
+        if !explored.contains(&location) {
+            return Some(location);
+        }
+
+        for (x, y) in utils::DIRECTIONS.iter() {
+            let next = location + utils::Coordinates { x: *x, y: *y };
+            let is_land = tile_data
+                .get(&next)
+                .map_or(false, |tile| tile::is_land(&tile.kind));
+
+            if is_land && visited.insert(next) {
+                frontier.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+pub fn is_near(a: &utils::Coordinates, b: &utils::Coordinates, range: i32) -> bool {
+    (a.x - b.x).abs() <= range && (a.y - b.y).abs() <= range
+}
+
+pub fn describe(order: &Order) -> String {
+    match order {
+        Order::MoveTo(target) => format!("Move to ({}, {})", target.x, target.y),
+        Order::FoundCity => "Found city".to_string(),
+        Order::Patrol(start, end) => {
+            format!("Patrol ({}, {}) - ({}, {})", start.x, start.y, end.x, end.y)
+        }
+        Order::Sentry => "Sentry".to_string(),
+        Order::Explore => "Explore".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{actions, headless, tile, unit};
+
+    #[test]
+    fn exploring_stops_at_water() {
+        let tile_data: HashMap<_, _> = (0..5)
+            .map(|x| {
+                let location = utils::Coordinates { x, y: 0 };
+                let kind = match x {
+                    3 => tile::TileKind::Ocean,
+                    _ => tile::TileKind::Desert,
+                };
+                let tile = tile::Tile {
+                    location,
+                    kind,
+                    height: 0.,
+                    river: false,
+                    body: 0,
+                };
+                (location, tile)
+            })
+            .collect();
+        let mut explored: HashSet<_> = (0..2).map(|x| utils::Coordinates { x, y: 0 }).collect();
+        let start = utils::Coordinates { x: 0, y: 0 };
+
+        assert_eq!(
+            nearest_unexplored(start, &tile_data, &explored),
+            Some(utils::Coordinates { x: 2, y: 0 })
+        );
+        explored.insert(utils::Coordinates { x: 2, y: 0 });
+        assert_eq!(nearest_unexplored(start, &tile_data, &explored), None);
+    }
+
+    #[test]
+    fn settler_walks_and_founds_a_city() {
+        let mut app = headless::test_game(headless::test_settings(7));
+
+        let settler = app
+            .world
+            .query::<&Unit>()
+            .iter(&app.world)
+            .find(|unit| unit.owner == Some(0))
+            .unwrap()
+            .clone();
+        let site = app
+            .world
+            .query::<&tile::TileComponent>()
+            .iter(&app.world)
+            .filter(|tile| tile.owner.is_none() && unit::settler::can_settle(tile).is_ok())
+            .map(|tile| tile.tile.location)
+            .find(|location| {
+                is_near(location, &settler.location, 3) && !is_near(location, &settler.location, 2)
+            })
+            .unwrap();
+
+        headless::send(
+            &mut app,
+            0,
+            actions::Action::SetOrders(actions::SetOrders {
+                unit: settler.id,
+                orders: vec![Order::MoveTo(site), Order::FoundCity],
+                append: false,
+            }),
+        );
+        for _ in 0..4 {
+            headless::send(&mut app, 0, actions::Action::EndTurn);
+        }
+
+        let city = app
+            .world
+            .query::<&tile::TileComponent>()
+            .iter(&app.world)
+            .find(|tile| tile.tile.location == site)
+            .unwrap();
+        assert_eq!(city.owner, Some(0));
+        assert!(city.building.is_some());
+        assert!(app
+            .world
+            .query::<&Unit>()
+            .iter(&app.world)
+            .all(|unit| unit.id != settler.id));
+    }
+}
@@ -1,9 +1,10 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use super::UnitTrait;
-use crate::{actions, building, tile};
+use crate::{actions, tile, unit};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Settler {}
 
 impl Default for Settler {
@@ -22,37 +23,50 @@ pub fn init_resources<'a>(asset_server: &Res<AssetServer>) -> SettlerResources {
     SettlerResources { mesh }
 }
 
+pub fn can_settle(tile: &tile::TileComponent) -> Result<(), String> {
+    if !tile::is_settleable(&tile.tile.kind) {
+        return Err("Can't build on this tile".to_string());
+    }
+    if tile.building.is_some() {
+        return Err("Can't build over a building".to_string());
+    }
+
+    Ok(())
+}
+
+/// Checks that `empire` may turn the settler into a city on `tile`, the tile
+/// the settler stands on
+pub fn can_found_city(
+    unit: &unit::Unit,
+    tile: &tile::TileComponent,
+    empire: i32,
+) -> Result<(), String> {
+    let unit::UnitKind::Settler(_) = unit.kind else {
+        return Err("Only settlers can found cities".to_string());
+    };
+    if unit.owner != Some(empire) {
+        return Err("Unit belongs to another empire".to_string());
+    }
+    if tile.owner.map_or(false, |tile_owner| tile_owner != empire) {
+        return Err("Tile is owned by another empire".to_string());
+    }
+
+    can_settle(tile)
+}
+
 impl UnitTrait for Settler {
     fn tile_action(
         &self,
-        tile: Mut<tile::TileComponent>,
-        unit_entity: Entity,
-        tile_entity: Entity,
+        tile: &tile::TileComponent,
+        unit: &unit::Unit,
         acting_empire: i32,
     ) -> Vec<actions::Action> {
-        if !tile::is_settleable(&tile.tile.kind) {
-            println!("Can't build on this tile");
+        if let Err(reason) = can_found_city(unit, tile, acting_empire) {
+            println!("{}", reason);
 
             return vec![];
         }
-        match &tile.building {
-            None => {
-                let city = building::Building::City(default());
-
-                vec![
-                    actions::Action::Build(actions::Build {
-                        building: city,
-                        tile_entity: tile_entity,
-                        owner: acting_empire,
-                    }),
-                    actions::Action::KillUnit(unit_entity),
-                ]
-            }
-            Some(_) => {
-                println!("Can't build over a building");
-
-                vec![]
-            }
-        }
+
+        vec![actions::Action::FoundCity(unit.id)]
     }
 }
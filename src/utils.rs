@@ -1,9 +1,10 @@
 use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
 use std::ops::{Add, AddAssign};
 
 use crate::resource;
 
-#[derive(PartialEq, Eq, Clone, Debug, Hash, Copy, Component)]
+#[derive(PartialEq, Eq, Clone, Debug, Hash, Copy, Component, Serialize, Deserialize)]
 pub struct Coordinates {
     pub x: i32,
     pub y: i32,
@@ -55,3 +56,34 @@ pub struct Inventory {
     pub items: HashMap<resource::Resource, i32>,
     pub capacity: i32,
 }
+
+impl Inventory {
+    pub fn can_afford(&self, cost: &[(resource::Resource, i32)]) -> Result<(), String> {
+        for (resource, amount) in cost {
+            if self.items.get(resource).unwrap_or(&0) < amount {
+                return Err(format!("Not enough {:?}", resource));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the cost from the inventory, check `can_afford` first
+    pub fn spend(&mut self, cost: &[(resource::Resource, i32)]) {
+        for (resource, amount) in cost {
+            *self.items.entry(resource.clone()).or_insert(0) -= amount;
+        }
+    }
+}
+
+/// The option after `current`, the first one after the last or when `current`
+/// is not an option
+pub fn next_in_cycle<T: PartialEq + Copy>(options: &[T], current: T) -> T {
+    let index = options
+        .iter()
+        .position(|option| *option == current)
+        .map(|index| (index + 1) % options.len())
+        .unwrap_or(0);
+
+    options[index]
+}
@@ -1,38 +1,221 @@
 use bevy::prelude::*;
-use bevy::utils::HashMap;
+use bevy::utils::{HashMap, HashSet};
 use noise::{NoiseFn, Simplex};
-use rand::seq::IteratorRandom;
+use rand::{
+    rngs::StdRng,
+    seq::{IteratorRandom, SliceRandom},
+    Rng, SeedableRng,
+};
 
-use crate::config::CONFIG;
+use crate::config;
 use crate::tile::TILE_SIZE;
-use crate::{building, colors, controls, empire, tile, unit, utils};
+use crate::{
+    building, colors, controls, empire, geography, map_image, scenario, tile, unit, utils,
+};
 
 const WATER_LEVEL: f32 = 0.2;
+/// One river is started for about every this many tiles
+const TILES_PER_RIVER: usize = 300;
+/// Tiles between the centers of neighbouring islands of an archipelago
+const ISLAND_SPACING: i32 = 10;
+
+/// World height of a tile from its generator height, water is flat
+pub fn tile_height(height: f64) -> f32 {
+    (height as f32 * TILE_SIZE * 2.).max(WATER_LEVEL * TILE_SIZE * 2.)
+}
+
+/// Generator height of a tile, the inverse of `tile_height` above water
+pub fn generator_height(height: f32) -> f64 {
+    (height / (TILE_SIZE * 2.)) as f64
+}
+
+/// Height in generator units, -1 to 1, for tiles placed by hand
+pub fn typical_height(kind: &tile::TileKind) -> f64 {
+    match kind {
+        tile::TileKind::Ocean => -0.5,
+        tile::TileKind::Shallows => 0.,
+        tile::TileKind::Hills => 0.45,
+        tile::TileKind::Mountain => 0.6,
+        tile::TileKind::SnowyMountain => 0.8,
+        _ => 0.3,
+    }
+}
 
-fn compute_tile_kind(height: f64, biome: f64) -> tile::TileKind {
+/// `temperature` goes from 0 at the poles to 1 at the equator, `moisture`
+/// from -1 to 1
+fn compute_tile_kind(height: f64, temperature: f64, moisture: f64) -> tile::TileKind {
     if height < -0.2 {
         return tile::TileKind::Ocean;
     } else if height <= WATER_LEVEL as f64 {
         return tile::TileKind::Shallows;
-    } else if height < 0.5 {
-        if biome < 0. {
-            return tile::TileKind::Desert;
-        } else {
-            return tile::TileKind::Forest;
-        }
-    } else if height < 0.7 {
+    } else if height >= 0.7 {
+        return tile::TileKind::SnowyMountain;
+    } else if height >= 0.5 {
         return tile::TileKind::Mountain;
+    } else if height >= 0.4 {
+        return tile::TileKind::Hills;
+    }
+
+    if temperature < 0.15 {
+        tile::TileKind::Snow
+    } else if temperature < 0.3 {
+        tile::TileKind::Tundra
+    } else if moisture < -0.3 {
+        tile::TileKind::Desert
+    } else if moisture < -0.1 {
+        tile::TileKind::Plains
+    } else if moisture > 0.4 && height < 0.3 {
+        tile::TileKind::Swamp
+    } else if moisture > 0.2 && temperature > 0.7 {
+        tile::TileKind::Jungle
+    } else if moisture > 0.1 {
+        tile::TileKind::Forest
     } else {
-        return tile::TileKind::SnowyMountain;
+        tile::TileKind::Grassland
     }
 }
 
+/// Warmest in the middle row of the map, with noise so climate zones aren't
+/// straight bands
+fn compute_temperature(y: i32, y_count: i32, noise: f64) -> f64 {
+    let latitude = (2. * y as f64 / (y_count - 1).max(1) as f64 - 1.).abs();
+
+    (1. - latitude + 0.2 * noise).clamp(0., 1.)
+}
+
 fn scaled_simplex_2d(simplex: Simplex, x: f64, y: f64, scale: f64) -> f64 {
     simplex.get([x * scale, y * scale])
 }
 
-pub fn spawn_tile_data(x_count: i32, y_count: i32) -> Vec<tile::TileComponent> {
-    let simplex_2d = Simplex::new(2);
+/// Simplex noise summed over octaves of doubling frequency, still in -1..1
+fn fractal_simplex_2d(simplex: Simplex, x: f64, y: f64, scale: f64, octaves: u32) -> f64 {
+    let mut total = 0.;
+    let mut amplitude = 1.;
+    let mut amplitude_sum = 0.;
+
+    for octave in 0..octaves {
+        let frequency = scale * 2f64.powi(octave as i32);
+        total += amplitude * scaled_simplex_2d(simplex, x, y, frequency);
+        amplitude_sum += amplitude;
+        amplitude /= 2.;
+    }
+
+    total / amplitude_sum
+}
+
+/// Noise scale, octaves and share of land tiles of a map type
+fn map_shape(map_type: config::MapType) -> (f64, u32, f64) {
+    match map_type {
+        config::MapType::Continents => (0.03, 4, 0.4),
+        config::MapType::Archipelago => (0.08, 3, 0.25),
+        config::MapType::Pangaea => (0.03, 4, 0.45),
+        config::MapType::InlandSea => (0.04, 3, 0.55),
+    }
+}
+
+/// Added to the noise to shape the map, `distance` is 0 in the center and 1
+/// in the corners
+fn height_mask(map_type: config::MapType, distance: f64) -> f64 {
+    match map_type {
+        config::MapType::Continents => -0.5 * distance.powi(3),
+        config::MapType::Archipelago => -0.3 * distance.powi(2),
+        config::MapType::Pangaea => 0.8 - 1.6 * distance,
+        config::MapType::InlandSea => 1.2 * distance - 0.8,
+    }
+}
+
+/// Centers of the islands of an archipelago, one somewhere in every cell of
+/// a grid so the islands are spread over the whole map
+fn island_centers(x_count: i32, y_count: i32, seed: u32) -> Vec<(f64, f64)> {
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+    let spacing = ISLAND_SPACING as f64;
+    let jitter = spacing / 4.;
+
+    let mut centers = vec![];
+    for cell_x in 0..(x_count + ISLAND_SPACING - 1) / ISLAND_SPACING {
+        for cell_y in 0..(y_count + ISLAND_SPACING - 1) / ISLAND_SPACING {
+            centers.push((
+                (cell_x as f64 + 0.5) * spacing + rng.gen_range(-jitter..jitter),
+                (cell_y as f64 + 0.5) * spacing + rng.gen_range(-jitter..jitter),
+            ));
+        }
+    }
+
+    centers
+}
+
+/// Raw heights of every tile, shifted so the map type's share of tiles ends
+/// up above the water level
+fn compute_heights(
+    seed: u32,
+    x_count: i32,
+    y_count: i32,
+    map_type: config::MapType,
+) -> HashMap<utils::Coordinates, f64> {
+    let simplex = Simplex::new(seed);
+    let (scale, octaves, land_ratio) = map_shape(map_type);
+
+    // Archipelagos sink between their islands, so the islands stay apart
+    let islands = match map_type {
+        config::MapType::Archipelago => island_centers(x_count, y_count, seed),
+        _ => vec![],
+    };
+
+    let mut heights = HashMap::new();
+    for x in 0..x_count {
+        for y in 0..y_count {
+            let dx = 2. * x as f64 / (x_count - 1).max(1) as f64 - 1.;
+            let dy = 2. * y as f64 / (y_count - 1).max(1) as f64 - 1.;
+            let distance = (dx * dx + dy * dy).sqrt() / 2f64.sqrt();
+
+            let noise = fractal_simplex_2d(simplex, x as f64, y as f64, scale, octaves);
+            let island = islands
+                .iter()
+                .map(|(center_x, center_y)| (x as f64 - center_x).hypot(y as f64 - center_y))
+                .min_by(|a, b| a.total_cmp(b))
+                .map_or(0., |distance| -3. * distance / ISLAND_SPACING as f64);
+            heights.insert(
+                utils::Coordinates { x, y },
+                noise + island + height_mask(map_type, distance),
+            );
+        }
+    }
+
+    let mut sorted: Vec<f64> = heights.values().copied().collect();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let water_count = ((1. - land_ratio) * sorted.len() as f64) as usize;
+    let Some(shoreline) = sorted.get(water_count.min(sorted.len().saturating_sub(1))) else {
+        return heights;
+    };
+    let offset = WATER_LEVEL as f64 + 1e-6 - shoreline;
+
+    for height in heights.values_mut() {
+        *height += offset;
+    }
+
+    heights
+}
+
+pub fn spawn_tile_data(
+    x_count: i32,
+    y_count: i32,
+    seed: u32,
+    map_type: config::MapType,
+) -> Vec<tile::TileComponent> {
+    let heights = compute_heights(seed, x_count, y_count, map_type);
+
+    tiles_from_heights(&heights, x_count, y_count, seed)
+}
+
+/// Turns a height field into tiles, climate and rivers still come from the
+/// seed
+pub fn tiles_from_heights(
+    heights: &HashMap<utils::Coordinates, f64>,
+    x_count: i32,
+    y_count: i32,
+    seed: u32,
+) -> Vec<tile::TileComponent> {
+    let simplex_2d = Simplex::new(seed);
 
     let mut tiles = HashMap::new();
     for x in 0..x_count {
@@ -40,16 +223,25 @@ pub fn spawn_tile_data(x_count: i32, y_count: i32) -> Vec<tile::TileComponent> {
             let x_float = x as f64;
             let y_float = y as f64;
 
-            let height = scaled_simplex_2d(simplex_2d, x_float, y_float, 0.05 / 2.);
-            let biome = scaled_simplex_2d(simplex_2d, x_float, y_float, 0.02 / 2.);
+            let height = heights
+                .get(&utils::Coordinates { x, y })
+                .copied()
+                .unwrap_or(-1.);
+            let moisture = scaled_simplex_2d(simplex_2d, x_float, y_float, 0.02 / 2.);
+            // Far away samples of the same noise are unrelated to the ones above
+            let temperature_noise =
+                scaled_simplex_2d(simplex_2d, x_float + 1000., y_float + 1000., 0.05);
+            let temperature = compute_temperature(y, y_count, temperature_noise);
 
-            let kind: tile::TileKind = compute_tile_kind(height, biome);
+            let kind: tile::TileKind = compute_tile_kind(height, temperature, moisture);
 
             let tile = tile::TileComponent {
                 tile: tile::Tile {
                     location: utils::Coordinates { x, y },
-                    height: (height as f32 * TILE_SIZE * 2.).max(WATER_LEVEL * TILE_SIZE * 2.),
+                    height: tile_height(height),
                     kind: kind.clone(),
+                    river: false,
+                    body: 0,
                 },
                 owner: None,
                 building: None,
@@ -85,35 +277,209 @@ pub fn spawn_tile_data(x_count: i32, y_count: i32) -> Vec<tile::TileComponent> {
         }
     }
 
+    add_rivers(&mut tiles, seed);
+
     tiles.into_iter().map(|(_, tile)| tile).collect()
 }
 
-fn add_empire_data(tile_data: &mut Vec<tile::TileComponent>, number_of_empires: i32) {
-    let mut spawned_empires = 0;
-    let mut rng = rand::thread_rng();
+/// Lets rivers run downhill from randomly picked mountains. A river ends in
+/// the sea or in another river, rivers stuck in a dip are dropped
+fn add_rivers(tiles: &mut HashMap<utils::Coordinates, tile::TileComponent>, seed: u32) {
+    let mut rng = StdRng::seed_from_u64(seed as u64);
 
-    let mut max_attempts = 1000;
-    while spawned_empires < number_of_empires && max_attempts > 0 {
-        let chosen_tile = tile_data.iter_mut().choose(&mut rng).unwrap();
+    let mut mountains: Vec<utils::Coordinates> = tiles
+        .values()
+        .filter(|tile| tile.tile.kind == tile::TileKind::Mountain)
+        .map(|tile| tile.tile.location)
+        .collect();
+    mountains.sort_by_key(|location| (location.x, location.y));
 
-        if tile::is_spawnable(&chosen_tile.tile.kind) && chosen_tile.owner.is_none() {
-            chosen_tile.owner = Some(spawned_empires);
-            chosen_tile.building = Some(building::Building::Capital(default()));
-            spawned_empires += 1;
+    let river_count = tiles.len() / TILES_PER_RIVER;
+    let sources = mountains.into_iter().choose_multiple(&mut rng, river_count);
+
+    for source in sources {
+        let mut path = vec![source];
+
+        loop {
+            let current = tiles.get(path.last().unwrap()).unwrap();
+            if path.len() > 1 && current.tile.river {
+                break;
+            }
+
+            let lowest = utils::DIRECTIONS
+                .iter()
+                .filter_map(|(x, y)| {
+                    tiles.get(&(current.tile.location + utils::Coordinates { x: *x, y: *y }))
+                })
+                .filter(|neighbor| neighbor.tile.height < current.tile.height)
+                .min_by(|a, b| a.tile.height.total_cmp(&b.tile.height));
+
+            match lowest {
+                Some(neighbor) if !tile::is_land(&neighbor.tile.kind) => break,
+                Some(neighbor) => path.push(neighbor.tile.location),
+                None => {
+                    path.clear();
+                    break;
+                }
+            }
         }
 
-        max_attempts -= 1;
+        for location in path {
+            tiles.get_mut(&location).unwrap().tile.river = true;
+        }
     }
-    if max_attempts == 0 {
-        panic!("Could not place all empires");
+}
+
+/// Capitals need at least this many land tiles on their landmass
+const MIN_LANDMASS_SIZE: usize = 30;
+/// Tiles this close to a capital count towards its start score
+const START_RADIUS: i32 = 2;
+
+/// How good a capital on `center` would be: nearby land, yields and rivers
+fn start_score(
+    tiles: &HashMap<utils::Coordinates, &tile::TileComponent>,
+    center: utils::Coordinates,
+) -> i32 {
+    let mut score = 0;
+
+    for x in -START_RADIUS..=START_RADIUS {
+        for y in -START_RADIUS..=START_RADIUS {
+            let Some(tile) = tiles.get(&(center + utils::Coordinates { x, y })) else {
+                continue;
+            };
+            if !tile::is_land(&tile.tile.kind) {
+                continue;
+            }
+
+            let yields: i32 = tile::tile_yield(&tile.tile.kind)
+                .iter()
+                .map(|(_, amount)| amount)
+                .sum();
+            score += 1 + yields + tile.tile.river as i32;
+        }
     }
+
+    score
+}
+
+fn distance(a: &utils::Coordinates, b: &utils::Coordinates) -> i32 {
+    (a.x - b.x).abs().max((a.y - b.y).abs())
 }
 
-#[derive(Resource)]
+/// Picks the best scoring starts that keep their distance to each other. The
+/// distance and then the landmass size are relaxed until every empire fits,
+/// if the map is too small fewer capitals are placed. Returns their number
+fn add_empire_data(
+    tile_data: &mut Vec<tile::TileComponent>,
+    geography: &geography::Geography,
+    number_of_empires: i32,
+    seed: u32,
+) -> i32 {
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+    let wanted = number_of_empires.max(0) as usize;
+
+    let mut candidates: Vec<(i32, usize, utils::Coordinates)> = {
+        let tiles: HashMap<_, _> = tile_data
+            .iter()
+            .map(|tile| (tile.tile.location, tile))
+            .collect();
+
+        tile_data
+            .iter()
+            .filter(|tile| tile::is_spawnable(&tile.tile.kind) && tile.owner.is_none())
+            .map(|tile| {
+                let location = tile.tile.location;
+                let size = geography.landmass_size(&tile.tile);
+                (start_score(&tiles, location), size, location)
+            })
+            .collect()
+    };
+    // The seed breaks ties between equally good starts
+    candidates.shuffle(&mut rng);
+    candidates.sort_by_key(|(score, _, _)| -score);
+
+    let area = tile_data.len() as f64;
+    let mut min_distance = ((area / wanted.max(1) as f64).sqrt() / 2.).max(3.) as i32;
+    let mut min_size = MIN_LANDMASS_SIZE;
+
+    let mut capitals: Vec<utils::Coordinates> = vec![];
+    loop {
+        capitals.clear();
+        for (_, size, location) in candidates.iter() {
+            if capitals.len() == wanted {
+                break;
+            }
+            let far_enough = capitals
+                .iter()
+                .all(|capital| distance(capital, location) >= min_distance);
+            if *size >= min_size && far_enough {
+                capitals.push(*location);
+            }
+        }
+
+        if capitals.len() == wanted || (min_distance == 1 && min_size == 0) {
+            break;
+        }
+        if min_distance > 1 {
+            min_distance -= 1;
+        } else {
+            min_size /= 2;
+        }
+    }
+
+    if capitals.len() < wanted {
+        println!(
+            "Only found room for {} of {} empires",
+            capitals.len(),
+            wanted
+        );
+    }
+
+    // Empire ids are handed out randomly, so the first empire isn't always
+    // the one with the best start
+    capitals.shuffle(&mut rng);
+    let placed = capitals.len() as i32;
+    for (empire, capital) in capitals.into_iter().enumerate() {
+        let tile = tile_data
+            .iter_mut()
+            .find(|tile| tile.tile.location == capital)
+            .unwrap();
+
+        tile.owner = Some(empire as i32);
+        tile.building = Some(building::Building::Capital(default()));
+    }
+
+    placed
+}
+
+#[derive(Resource, Default)]
 pub struct WorldState {
+    pub turn: u32,
     pub tile_entities: HashMap<utils::Coordinates, Entity>,
     pub tile_data: HashMap<utils::Coordinates, tile::Tile>,
     pub empires: HashMap<i32, Entity>,
+    pub units: HashMap<unit::UnitId, Entity>,
+    pub occupancy: unit::occupancy::Occupancy,
+    pub geography: geography::Geography,
+    next_unit_id: u32,
+}
+
+impl WorldState {
+    pub fn next_unit_id(&mut self) -> unit::UnitId {
+        let id = unit::UnitId(self.next_unit_id);
+        self.next_unit_id += 1;
+        id
+    }
+
+    /// The id the next call to `next_unit_id` hands out
+    pub fn peek_unit_id(&self) -> unit::UnitId {
+        unit::UnitId(self.next_unit_id)
+    }
+
+    /// Hands out ids from `id` again, after undoing the spawn of a unit
+    pub fn rewind_unit_id(&mut self, id: unit::UnitId) {
+        self.next_unit_id = id.0;
+    }
 }
 
 pub fn spawn(
@@ -123,33 +489,116 @@ pub fn spawn(
     mut materials: ResMut<Assets<ColorMaterial>>,
     camera: Query<&mut Transform, With<Camera3d>>,
     unit_resources: Res<unit::UnitResources>,
+    mut settings: ResMut<config::GameSettings>,
 ) {
     let mut world_state = WorldState {
+        turn: 0,
         tile_entities: HashMap::new(),
         tile_data: HashMap::new(),
         empires: HashMap::new(),
+        units: HashMap::new(),
+        occupancy: default(),
+        geography: default(),
+        next_unit_id: 0,
     };
-    const NUMBER_OF_EMPIRES: i32 = 10;
-
-    let (x_count, y_count) = CONFIG.world_size;
-
-    let mut tile_data = spawn_tile_data(x_count, y_count);
-    add_empire_data(&mut tile_data, NUMBER_OF_EMPIRES);
+    let scenario = settings
+        .scenario
+        .clone()
+        .and_then(|path| match scenario::load(&path) {
+            Ok(scenario) => Some(scenario),
+            Err(error) => {
+                println!("Could not load scenario {}: {}", path, error);
+                None
+            }
+        });
+    if let Some(scenario) = &scenario {
+        scenario::apply_settings(scenario, &mut settings);
+    }
+    let (x_count, y_count) = settings.world_size;
+
+    let generated = || spawn_tile_data(x_count, y_count, settings.seed, settings.map_type);
+    let mut tile_data = match (&scenario, &settings.starting_map) {
+        (Some(scenario), _) if !scenario.tiles.is_empty() => scenario.tiles.clone(),
+        (_, Some(path)) => map_image::import(path, settings.seed).unwrap_or_else(|error| {
+            println!("Could not import map {}: {}", path, error);
+            generated()
+        }),
+        _ => generated(),
+    };
+    // Tiles are sorted so spawn order, and with it unit ids, only depend on the seed
+    tile_data.sort_by_key(|tile| (tile.tile.location.x, tile.tile.location.y));
+    // Imported and scenario maps bring their own size
+    settings.world_size = tile_data.iter().fold((0, 0), |(width, height), tile| {
+        (
+            width.max(tile.tile.location.x + 1),
+            height.max(tile.tile.location.y + 1),
+        )
+    });
+    let geography = geography::analyze(&mut tile_data);
+
+    // Random games start with a settler on every capital
+    let starting_units = match &scenario {
+        Some(scenario) => {
+            scenario::place_cities(scenario, &mut tile_data);
+            scenario.units.clone()
+        }
+        None => {
+            // Empires without a capital are left out of the game
+            settings.number_of_empires = add_empire_data(
+                &mut tile_data,
+                &geography,
+                settings.number_of_empires,
+                settings.seed,
+            );
+            tile_data
+                .iter()
+                .filter_map(|tile| {
+                    Some(scenario::UnitSetup {
+                        kind: unit::UnitKind::Settler(default()),
+                        location: tile.tile.location,
+                        owner: tile.owner?,
+                        orders: vec![],
+                    })
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+    world_state.geography = geography;
+    let number_of_empires = settings.number_of_empires;
 
     let mut color_list = vec![];
 
-    for i in 0..NUMBER_OF_EMPIRES {
-        let color = materials.add(colors::bright_hue(i as f32 / NUMBER_OF_EMPIRES as f32));
+    for i in 0..number_of_empires {
+        let setup = scenario
+            .as_ref()
+            .and_then(|scenario| scenario.empires.get(i as usize));
+        let color = materials.add(colors::bright_hue(i as f32 / number_of_empires as f32));
+        let mut empire = empire::Empire {
+            id: i as i32,
+            name: setup.map_or(format!("Empire {}", i), |setup| setup.name.clone()),
+            color: color.clone(),
+            inventory: utils::Inventory {
+                items: setup.map_or(HashMap::new(), |setup| setup.inventory.clone()),
+                capacity: 100,
+            },
+            explored: HashSet::new(),
+        };
+        // Empires start out knowing the surroundings of their tiles and units
+        let owned_tiles = tile_data
+            .iter()
+            .filter(|tile| tile.owner == Some(i))
+            .map(|tile| tile.tile.location);
+        let unit_locations = starting_units
+            .iter()
+            .filter(|setup| setup.owner == i)
+            .map(|setup| setup.location);
+        for location in owned_tiles.chain(unit_locations) {
+            empire.reveal(location);
+        }
+
         let empire_entity = commands
             .spawn((
-                empire::Empire {
-                    id: i as i32,
-                    color: color.clone(),
-                    inventory: utils::Inventory {
-                        items: HashMap::new(),
-                        capacity: 100,
-                    },
-                },
+                empire,
                 TransformBundle::default(),
                 InheritedVisibility::default(),
             ))
@@ -167,20 +616,8 @@ pub fn spawn(
             .tile_data
             .insert(tile.tile.location, tile.tile.clone());
 
-        if let Some(empire_id) = tile.owner {
-            let unit_bundle = unit::make_bundle(
-                unit::Unit {
-                    location: tile.tile.location,
-                    owner: Some(empire_id),
-                    ..Default::default()
-                },
-                &unit_resources,
-                &world_state.tile_data,
-            );
-            commands.spawn(unit_bundle);
-            if empire_id == 0 {
-                camera_spawn_point = Some(utils::to_transform(&tile.tile.location));
-            }
+        if tile.owner == Some(0) && camera_spawn_point.is_none() {
+            camera_spawn_point = Some(utils::to_transform(&tile.tile.location));
         }
 
         let tile_bundle = tile::make_bundle(&tile_resources, tile);
@@ -197,9 +634,151 @@ pub fn spawn(
         }
     }
 
+    for setup in starting_units {
+        let (owner, location) = (setup.owner, setup.location);
+        if let Err(reason) =
+            scenario::spawn_unit(setup, &mut commands, &mut world_state, &unit_resources)
+        {
+            println!(
+                "Can't place unit at ({}, {}): {}",
+                location.x, location.y, reason
+            );
+            continue;
+        }
+        if owner == 0 && camera_spawn_point.is_none() {
+            camera_spawn_point = Some(utils::to_transform(&location));
+        }
+    }
+
+    if let Some(scenario) = scenario {
+        commands.insert_resource(scenario::ActiveScenario {
+            turn_limit: scenario.turn_limit,
+            triggers: scenario.triggers,
+            evaluated_turn: None,
+            messages: vec![],
+            winner: None,
+        });
+    }
+
     commands.insert_resource(world_state);
 
     if let Some(camera_spawn_point) = camera_spawn_point {
         controls::move_camera_to(camera, camera_spawn_point);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn climate_decides_lowland_kind() {
+        assert_eq!(compute_tile_kind(0.3, 0.1, 0.), tile::TileKind::Snow);
+        assert_eq!(compute_tile_kind(0.3, 0.9, 0.3), tile::TileKind::Jungle);
+        assert_eq!(compute_tile_kind(0.3, 0.5, -0.5), tile::TileKind::Desert);
+        assert_eq!(compute_tile_kind(0.45, 0.1, 0.), tile::TileKind::Hills);
+        assert_eq!(compute_temperature(0, 61, 0.), 0.);
+        assert_eq!(compute_temperature(30, 61, 0.), 1.);
+
+        let mut kinds: Vec<_> = spawn_tile_data(60, 60, 3, config::MapType::Continents)
+            .iter()
+            .map(|tile| tile::tile_string(&tile.tile.kind))
+            .collect();
+        kinds.sort();
+        kinds.dedup();
+        assert!(kinds.len() >= 8);
+    }
+
+    #[test]
+    fn map_types_keep_their_land_ratio() {
+        for map_type in config::MAP_TYPES {
+            let mut tiles = spawn_tile_data(60, 60, 5, map_type);
+            let land = tiles
+                .iter()
+                .filter(|tile| tile::is_land(&tile.tile.kind))
+                .count();
+
+            let (_, _, land_ratio) = map_shape(map_type);
+            let actual = land as f64 / tiles.len() as f64;
+            assert!(
+                (actual - land_ratio).abs() < 0.01,
+                "{:?}: {}",
+                map_type,
+                actual
+            );
+
+            // Landmasses holding a tenth of the land, and the largest one
+            let landmasses: Vec<_> = geography::analyze(&mut tiles)
+                .bodies
+                .into_iter()
+                .filter(|body| body.land)
+                .map(|body| body.size)
+                .collect();
+            let big = landmasses.iter().filter(|size| **size * 10 >= land).count();
+            let largest = *landmasses.iter().max().unwrap();
+            match map_type {
+                config::MapType::Pangaea => assert!(largest * 10 >= land * 9),
+                config::MapType::Continents => assert!(big >= 2, "{:?}", landmasses),
+                config::MapType::Archipelago => {
+                    assert!(landmasses.len() >= 20, "{:?}", landmasses);
+                    assert!(largest * 100 <= land * 15, "{:?}", landmasses);
+                }
+                config::MapType::InlandSea => (),
+            }
+        }
+    }
+
+    #[test]
+    fn capitals_keep_their_distance() {
+        let mut tile_data = spawn_tile_data(60, 60, 5, config::MapType::Continents);
+        tile_data.sort_by_key(|tile| (tile.tile.location.x, tile.tile.location.y));
+        let geography = geography::analyze(&mut tile_data);
+        assert_eq!(add_empire_data(&mut tile_data, &geography, 4, 5), 4);
+
+        let capitals: Vec<_> = tile_data
+            .iter()
+            .filter(|tile| tile.building.is_some())
+            .map(|tile| tile.tile.location)
+            .collect();
+        assert_eq!(capitals.len(), 4);
+        for a in capitals.iter() {
+            for b in capitals.iter().filter(|b| *b != a) {
+                assert!(distance(a, b) >= 3);
+            }
+        }
+
+        // Too many empires for the map only place as many capitals as fit
+        let mut tile_data = spawn_tile_data(8, 8, 5, config::MapType::Archipelago);
+        let geography = geography::analyze(&mut tile_data);
+        let placed = add_empire_data(&mut tile_data, &geography, 100, 5);
+        let capitals = tile_data
+            .iter()
+            .filter(|tile| tile.building.is_some())
+            .count();
+        assert!(placed < 100);
+        assert_eq!(placed as usize, capitals);
+    }
+
+    #[test]
+    fn rivers_flow_downhill_into_the_sea() {
+        let tiles: HashMap<_, _> = spawn_tile_data(60, 60, 3, config::MapType::Continents)
+            .into_iter()
+            .map(|tile| (tile.tile.location, tile.tile))
+            .collect();
+
+        let rivers: Vec<_> = tiles.values().filter(|tile| tile.river).collect();
+        assert!(!rivers.is_empty());
+
+        // Every river tile drains into lower river or the sea
+        for river in rivers {
+            assert!(tile::is_land(&river.kind));
+            assert!(utils::DIRECTIONS.iter().any(|(x, y)| {
+                let neighbor = tiles.get(&(river.location + utils::Coordinates { x: *x, y: *y }));
+                neighbor.map_or(false, |neighbor| {
+                    neighbor.height < river.height
+                        && (neighbor.river || !tile::is_land(&neighbor.kind))
+                })
+            }));
+        }
+    }
+}